@@ -132,6 +132,7 @@ unsafe fn memory_read_sequential_single_thread_vectorized_avx2(vec: &[Int]) -> u
 
 #[inline(never)]
 #[no_mangle]
+#[allow(clippy::needless_return)]
 fn memory_read_sequential_single_thread_non_vectorized(vec: &[Int]) -> u64 {
     #[cfg(target_arch = "x86_64")]
     {
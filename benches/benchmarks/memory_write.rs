@@ -0,0 +1,203 @@
+use criterion::*;
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::{asm, x86_64::*};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::Duration;
+
+type Int = u64;
+
+struct ThreadedMemoryWriteBenchmark {
+    start: Arc<Barrier>,
+    end: Arc<Barrier>,
+}
+
+impl ThreadedMemoryWriteBenchmark {
+    fn new(
+        core_ids: Arc<Vec<core_affinity::CoreId>>,
+        size_in_elements: usize,
+        f: fn(&mut [Int], Int),
+    ) -> Self {
+        let num_cores = core_ids.len();
+        let slice_size = size_in_elements / num_cores;
+
+        let start = Arc::new(Barrier::new(num_cores + 1));
+        let end = Arc::new(Barrier::new(num_cores + 1));
+        let done_allocating = Arc::new(Barrier::new(num_cores + 1));
+
+        for (index, core) in core_ids.iter().copied().enumerate() {
+            let t_start = start.clone();
+            let t_end = end.clone();
+            let t_done_allocating = done_allocating.clone();
+
+            thread::spawn(move || {
+                // Same as the reads: one thread per core, each first-touching its own slice.
+                core_affinity::set_for_current(core);
+
+                let range_start = slice_size * index;
+                let range_end = if index + 1 == num_cores {
+                    size_in_elements
+                } else {
+                    range_start + slice_size
+                };
+                let mut vec: Vec<Int> = (range_start..range_end).map(|i| i as Int).collect();
+
+                t_done_allocating.wait();
+                let mut value: Int = 1;
+                loop {
+                    t_start.wait();
+                    f(&mut vec, value);
+                    black_box(vec.as_ptr());
+                    value = value.wrapping_add(1);
+                    t_end.wait();
+                }
+            });
+        }
+
+        done_allocating.wait();
+
+        Self { start, end }
+    }
+
+    fn run(&self) {
+        self.start.wait();
+        self.end.wait();
+    }
+}
+
+#[inline(never)]
+#[no_mangle]
+fn memory_write_sequential_single_thread_vectorized(vec: &mut [Int], value: Int) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            unsafe {
+                return memory_write_sequential_single_thread_vectorized_avx2(vec, value);
+            }
+        }
+    }
+
+    for slot in vec.iter_mut() {
+        *slot = value;
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn memory_write_sequential_single_thread_vectorized_avx2(vec: &mut [Int], value: Int) {
+    let lanes = _mm256_set1_epi64x(value as i64);
+    let mut i = 0usize;
+    let ptr = vec.as_mut_ptr();
+
+    while i + 16 <= vec.len() {
+        _mm256_storeu_si256(ptr.add(i) as *mut __m256i, lanes);
+        _mm256_storeu_si256(ptr.add(i + 4) as *mut __m256i, lanes);
+        _mm256_storeu_si256(ptr.add(i + 8) as *mut __m256i, lanes);
+        _mm256_storeu_si256(ptr.add(i + 12) as *mut __m256i, lanes);
+        i += 16;
+    }
+
+    while i < vec.len() {
+        *ptr.add(i) = value;
+        i += 1;
+    }
+}
+
+#[inline(never)]
+#[no_mangle]
+#[allow(clippy::needless_return)]
+fn memory_write_sequential_single_thread_non_vectorized(vec: &mut [Int], value: Int) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        unsafe {
+            return memory_write_sequential_single_thread_non_vectorized_x86(vec, value);
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        let ptr = vec.as_mut_ptr();
+
+        for i in 0..vec.len() {
+            // Volatile scalar stores keep the compiler from turning this into a vectorized fill.
+            unsafe { std::ptr::write_volatile(ptr.add(i), value) };
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline(never)]
+unsafe fn memory_write_sequential_single_thread_non_vectorized_x86(vec: &mut [Int], value: Int) {
+    let ptr = vec.as_mut_ptr();
+    let remaining = vec.len();
+
+    asm!(
+        "test {remaining}, {remaining}",
+        "je 3f",
+        "2:",
+        "mov qword ptr [{ptr}], {value}",
+        "add {ptr}, 8",
+        "dec {remaining}",
+        "jne 2b",
+        "3:",
+        ptr = inout(reg) ptr => _,
+        remaining = inout(reg) remaining => _,
+        value = in(reg) value,
+        options(nostack),
+    );
+}
+
+fn memory_write_benchmark(c: &mut Criterion) {
+    let bytes_per_iteration = 8usize;
+    let size_bytes = n_gib_bytes!(1) as usize;
+    let size_in_elements = size_bytes / bytes_per_iteration;
+    let mut vec: Vec<Int> = (0..size_in_elements).map(|i| i as Int).collect();
+
+    // Keep all-core benchmarks on a single hardware thread per core after `./run` disables HT.
+    let core_ids = Arc::new(core_affinity::get_core_ids().unwrap());
+    let threaded_non_vectorized = ThreadedMemoryWriteBenchmark::new(
+        core_ids.clone(),
+        size_in_elements,
+        memory_write_sequential_single_thread_non_vectorized,
+    );
+    let threaded_vectorized = ThreadedMemoryWriteBenchmark::new(
+        core_ids.clone(),
+        size_in_elements,
+        memory_write_sequential_single_thread_vectorized,
+    );
+
+    let mut group = c.benchmark_group("memory_write");
+    group.sample_size(10);
+    group.warm_up_time(Duration::from_secs(10));
+    group.measurement_time(Duration::from_secs(10));
+    group.throughput(Throughput::Bytes(size_bytes as u64));
+    let mut value: Int = 1;
+    group.bench_function("1 thread, No SIMD", |b| {
+        b.iter(|| {
+            value = value.wrapping_add(1);
+            memory_write_sequential_single_thread_non_vectorized(&mut vec, value);
+            black_box(vec.as_ptr())
+        })
+    });
+    group.bench_function("1 thread, SIMD", |b| {
+        b.iter(|| {
+            value = value.wrapping_add(1);
+            memory_write_sequential_single_thread_vectorized(&mut vec, value);
+            black_box(vec.as_ptr())
+        })
+    });
+    group.bench_function(format!("{} threads, No SIMD", core_ids.len()), |b| {
+        b.iter(|| {
+            threaded_non_vectorized.run();
+        })
+    });
+    group.bench_function(format!("{} threads, SIMD", core_ids.len()), |b| {
+        b.iter(|| {
+            threaded_vectorized.run();
+        })
+    });
+    group.finish()
+}
+
+criterion_group!(benches, memory_write_benchmark);
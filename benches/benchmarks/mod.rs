@@ -1,2 +1,3 @@
 pub mod compressed_memory_read;
 pub mod memory_read;
+pub mod memory_write;
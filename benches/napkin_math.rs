@@ -5,5 +5,6 @@ use criterion::criterion_main;
 
 criterion_main! {
     benchmarks::memory_read::benches,
+    benchmarks::memory_write::benches,
     benchmarks::compressed_memory_read::benches,
 }
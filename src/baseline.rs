@@ -0,0 +1,203 @@
+// Saved results for `--save-baseline` / `--compare`, so a run on a new instance type can be
+// diffed against a previous one instead of eyeballing the text output.
+//
+// Each baseline is a JSON file keyed by benchmark name in `NAPKIN_BASELINE_DIR` (default
+// `target/napkin/baselines`, next to Criterion's `target/criterion`).
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// Changes smaller than this are reported as unchanged even when they're statistically significant,
+// same default as Criterion's `noise_threshold`.
+const NOISE_THRESHOLD: f64 = 0.02;
+
+// |t| above this is roughly a 99% confidence that the means differ.
+const T_CRITICAL: f64 = 2.576;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Entry {
+    pub ns_per_iteration: f64,
+    pub bytes_per_iteration: usize,
+    // Summary of the latency samples, which is what the significance test runs on.
+    pub sample_mean_ns: f64,
+    pub sample_stddev_ns: f64,
+    pub samples: usize,
+}
+
+pub type Baseline = BTreeMap<String, Entry>;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Improved,
+    Regressed,
+    Unchanged,
+}
+
+impl Verdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Verdict::Improved => "improved",
+            Verdict::Regressed => "regressed",
+            Verdict::Unchanged => "unchanged",
+        }
+    }
+}
+
+pub struct Comparison {
+    pub baseline_ns_per_iteration: f64,
+    // Relative change in time per iteration, negative is faster.
+    pub change: f64,
+    pub verdict: Verdict,
+}
+
+fn directory() -> PathBuf {
+    std::env::var("NAPKIN_BASELINE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("target/napkin/baselines"))
+}
+
+pub fn path(name: &str) -> PathBuf {
+    directory().join(format!("{}.json", name))
+}
+
+pub fn load(name: &str) -> io::Result<Baseline> {
+    let contents = fs::read_to_string(path(name))?;
+    serde_json::from_str(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+// Benchmarks report one at a time, so each one is merged into whatever is already on disk.
+pub fn save(name: &str, benchmark: &str, entry: Entry) -> io::Result<()> {
+    let mut baseline = match load(name) {
+        Ok(baseline) => baseline,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Baseline::new(),
+        Err(err) => return Err(err),
+    };
+    baseline.insert(benchmark.to_string(), entry);
+
+    fs::create_dir_all(directory())?;
+    fs::write(path(name), serde_json::to_string_pretty(&baseline)?)
+}
+
+pub fn compare(old: &Entry, new: &Entry) -> Comparison {
+    let change = (new.ns_per_iteration - old.ns_per_iteration) / old.ns_per_iteration;
+
+    // Welch's t-test on the latency samples, so a noisy benchmark needs a bigger change before
+    // we call it.
+    let standard_error = (old.sample_stddev_ns.powi(2) / old.samples.max(1) as f64
+        + new.sample_stddev_ns.powi(2) / new.samples.max(1) as f64)
+        .sqrt();
+    let significant = if standard_error > 0.0 {
+        ((new.sample_mean_ns - old.sample_mean_ns) / standard_error).abs() > T_CRITICAL
+    } else {
+        true
+    };
+
+    let verdict = if !significant || change.abs() < NOISE_THRESHOLD {
+        Verdict::Unchanged
+    } else if change < 0.0 {
+        Verdict::Improved
+    } else {
+        Verdict::Regressed
+    };
+
+    Comparison {
+        baseline_ns_per_iteration: old.ns_per_iteration,
+        change,
+        verdict,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(ns: f64, stddev: f64, samples: usize) -> Entry {
+        Entry {
+            ns_per_iteration: ns,
+            bytes_per_iteration: 0,
+            sample_mean_ns: ns,
+            sample_stddev_ns: stddev,
+            samples,
+        }
+    }
+
+    #[test]
+    fn compare_verdicts() {
+        // (case, old, new, verdict)
+        let cases = [
+            (
+                "identical",
+                entry(100.0, 0.0, 10),
+                entry(100.0, 0.0, 10),
+                Verdict::Unchanged,
+            ),
+            (
+                "no noise, slower",
+                entry(100.0, 0.0, 10),
+                entry(110.0, 0.0, 10),
+                Verdict::Regressed,
+            ),
+            (
+                "no noise, faster",
+                entry(100.0, 0.0, 10),
+                entry(90.0, 0.0, 10),
+                Verdict::Improved,
+            ),
+            (
+                "under the noise threshold",
+                entry(100.0, 0.0, 10),
+                entry(101.0, 0.0, 10),
+                Verdict::Unchanged,
+            ),
+            (
+                "noisy",
+                entry(100.0, 50.0, 10),
+                entry(110.0, 50.0, 10),
+                Verdict::Unchanged,
+            ),
+            // With 2 samples each the standard error is the stddev, so t is 10 / stddev.
+            (
+                "t just above critical",
+                entry(100.0, 3.8, 2),
+                entry(110.0, 3.8, 2),
+                Verdict::Regressed,
+            ),
+            (
+                "t just below critical",
+                entry(100.0, 3.9, 2),
+                entry(110.0, 3.9, 2),
+                Verdict::Unchanged,
+            ),
+            // No samples counts as one rather than dividing by zero: t is 10 / (3.8 * sqrt(2)).
+            (
+                "no samples",
+                entry(100.0, 3.8, 0),
+                entry(110.0, 3.8, 0),
+                Verdict::Unchanged,
+            ),
+        ];
+
+        for (case, old, new, verdict) in cases.iter() {
+            let comparison = compare(old, new);
+            assert!(
+                comparison.verdict == *verdict,
+                "{}: {} rather than {}",
+                case,
+                comparison.verdict.as_str(),
+                verdict.as_str()
+            );
+            assert_eq!(comparison.baseline_ns_per_iteration, old.ns_per_iteration);
+        }
+    }
+
+    #[test]
+    fn compare_change() {
+        let comparison = compare(&entry(100.0, 0.0, 10), &entry(125.0, 0.0, 10));
+        assert!((comparison.change - 0.25).abs() < 1e-12);
+        let comparison = compare(&entry(100.0, 0.0, 10), &entry(50.0, 0.0, 10));
+        assert!((comparison.change + 0.5).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,436 @@
+// What an allocation costs, asked of the system allocator and of jemalloc directly rather than
+// through `#[global_allocator]`, so one run compares them: malloc + free of small, medium and large
+// blocks, a block growing through realloc, blocks freed on another thread than the one that
+// allocated them, and small blocks on 1 up to every core at once. `report allocators` has them.
+//
+// Everything else allocates through the global allocator, jemalloc unless the binary is built with
+// `--features system_allocator`.
+
+use super::bandwidth::{cores, Pool};
+use super::{Benchmark, Category, Registry};
+use crate::report::format_size;
+use crate::{black_box, Control, Sampling};
+use core_affinity::CoreId;
+use jemallocator::Jemalloc;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io;
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender};
+use std::thread;
+
+// Blocks allocated before any of them is freed, so the allocator can't hand the same block back
+// every time.
+pub const ALLOCATIONS: usize = 64;
+// Rounds of `ALLOCATIONS` per thread per iteration of the threaded variants, enough to bury the
+// barriers the pool synchronizes on.
+const THREADED_ROUNDS: usize = 64;
+// What every thread does per iteration, `report allocators` divides by it for the time per
+// operation as each thread sees it.
+pub const THREADED_ALLOCATIONS: usize = ALLOCATIONS * THREADED_ROUNDS;
+const SMALL: usize = 64;
+const MEDIUM: usize = n_kib_bytes!(4) as usize;
+const LARGE: usize = n_mib_bytes!(1) as usize;
+// realloc doubles a block from the first to the second.
+const REALLOC_FROM: usize = 16;
+const REALLOC_TO: usize = n_mib_bytes!(1) as usize;
+
+pub fn register(registry: &mut Registry) {
+    for allocator in ALLOCATORS.iter() {
+        for op in ALLOC_OPS.iter() {
+            registry.register(Allocation::new(*allocator, *op));
+        }
+    }
+
+    // Powers of two and every core, the curve in between doesn't tell us much more.
+    let cores = cores();
+    for allocator in ALLOCATORS.iter() {
+        for threads in alloc_thread_counts(cores.len()) {
+            registry.register(AllocationThreaded::new(
+                *allocator,
+                cores[..threads].to_vec(),
+            ));
+        }
+    }
+}
+
+fn alloc_thread_counts(cores: usize) -> Vec<usize> {
+    let mut counts: Vec<usize> = (0..)
+        .map(|shift| 1 << shift)
+        .take_while(|threads| *threads < cores)
+        .collect();
+    counts.push(cores);
+    counts
+}
+
+#[derive(Clone, Copy)]
+pub enum Allocator {
+    System,
+    Jemalloc,
+}
+
+pub const ALLOCATORS: [Allocator; 2] = [Allocator::System, Allocator::Jemalloc];
+
+impl Allocator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Allocator::System => "system",
+            Allocator::Jemalloc => "jemalloc",
+        }
+    }
+
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let block = match self {
+            Allocator::System => System.alloc(layout),
+            Allocator::Jemalloc => Jemalloc.alloc(layout),
+        };
+        assert!(!block.is_null(), "{} out of memory", self.as_str());
+        // Otherwise the compiler is free to drop a malloc and free that nothing looks at.
+        black_box(block)
+    }
+
+    unsafe fn dealloc(&self, block: *mut u8, layout: Layout) {
+        match self {
+            Allocator::System => System.dealloc(block, layout),
+            Allocator::Jemalloc => Jemalloc.dealloc(block, layout),
+        }
+    }
+
+    unsafe fn realloc(&self, block: *mut u8, layout: Layout, size: usize) -> *mut u8 {
+        let block = match self {
+            Allocator::System => System.realloc(block, layout, size),
+            Allocator::Jemalloc => Jemalloc.realloc(block, layout, size),
+        };
+        assert!(!block.is_null(), "{} out of memory", self.as_str());
+        black_box(block)
+    }
+}
+
+// What the binary was built to allocate everything else with.
+pub fn global_allocator() -> Allocator {
+    if cfg!(feature = "system_allocator") {
+        Allocator::System
+    } else {
+        Allocator::Jemalloc
+    }
+}
+
+fn layout(size: usize) -> Layout {
+    Layout::from_size_align(size, 16).unwrap()
+}
+
+#[derive(Clone, Copy)]
+pub enum AllocOp {
+    Small,
+    Medium,
+    Large,
+    ReallocGrowth,
+    // Allocated here and freed by another thread, like a queue handing off messages.
+    CrossThreadFree,
+}
+
+pub const ALLOC_OPS: [AllocOp; 5] = [
+    AllocOp::Small,
+    AllocOp::Medium,
+    AllocOp::Large,
+    AllocOp::ReallocGrowth,
+    AllocOp::CrossThreadFree,
+];
+
+impl AllocOp {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AllocOp::Small => "small",
+            AllocOp::Medium => "medium",
+            AllocOp::Large => "large",
+            AllocOp::ReallocGrowth => "realloc_growth",
+            AllocOp::CrossThreadFree => "cross_thread_free",
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match self {
+            AllocOp::Small => format!("malloc + free {}", format_size(SMALL)),
+            AllocOp::Medium => format!("malloc + free {}", format_size(MEDIUM)),
+            AllocOp::Large => format!("malloc + free {}", format_size(LARGE)),
+            AllocOp::ReallocGrowth => format!(
+                "realloc doubling {} to {}",
+                format_size(REALLOC_FROM),
+                format_size(REALLOC_TO)
+            ),
+            AllocOp::CrossThreadFree => {
+                format!("malloc + free {} on another thread", format_size(SMALL))
+            }
+        }
+    }
+
+    // The mallocs and frees (or reallocs) in one iteration, `report allocators` divides by it.
+    pub fn ops_per_iteration(&self) -> usize {
+        match self {
+            AllocOp::ReallocGrowth => (REALLOC_TO / REALLOC_FROM).trailing_zeros() as usize,
+            _ => ALLOCATIONS,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            AllocOp::Medium => MEDIUM,
+            AllocOp::Large => LARGE,
+            _ => SMALL,
+        }
+    }
+}
+
+pub fn alloc_title(allocator: Allocator, op: AllocOp) -> String {
+    format!("{}, {}", op.title(), allocator.as_str())
+}
+
+pub fn alloc_threaded_title(allocator: Allocator, threads: usize) -> String {
+    let unit = if threads == 1 { "thread" } else { "threads" };
+    format!(
+        "malloc + free {}, {}, {} {}",
+        format_size(SMALL),
+        allocator.as_str(),
+        threads,
+        unit
+    )
+}
+
+// Allocates `ALLOCATIONS` blocks and frees them in the order they came.
+fn alloc_free(allocator: Allocator, blocks: &mut Vec<*mut u8>, size: usize) {
+    let layout = layout(size);
+    for _ in 0..ALLOCATIONS {
+        blocks.push(unsafe { allocator.alloc(layout) });
+    }
+    for block in blocks.drain(..) {
+        unsafe { allocator.dealloc(block, layout) };
+    }
+}
+
+// The thread freeing what the benchmark allocates. Batches go back and forth through the channels
+// so the vectors holding them are reused instead of allocated by the global allocator.
+pub struct CrossThreadFree {
+    to_free: Option<SyncSender<Vec<usize>>>,
+    freed: Receiver<Vec<usize>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl CrossThreadFree {
+    fn new(allocator: Allocator) -> CrossThreadFree {
+        let (to_free, batches) = sync_channel::<Vec<usize>>(1);
+        let (done, freed): (Sender<Vec<usize>>, _) = channel();
+        // Two batches, so one is being filled while the other is freed.
+        for _ in 0..2 {
+            done.send(Vec::with_capacity(ALLOCATIONS)).unwrap();
+        }
+        let thread = thread::spawn(move || {
+            let layout = layout(SMALL);
+            for mut batch in batches.iter() {
+                for block in batch.drain(..) {
+                    unsafe { allocator.dealloc(block as *mut u8, layout) };
+                }
+                if done.send(batch).is_err() {
+                    return;
+                }
+            }
+        });
+
+        CrossThreadFree {
+            to_free: Some(to_free),
+            freed,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for CrossThreadFree {
+    fn drop(&mut self) {
+        self.to_free.take();
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap();
+        }
+    }
+}
+
+pub enum AllocationState {
+    Blocks(Vec<*mut u8>),
+    CrossThread(CrossThreadFree),
+}
+
+pub struct Allocation {
+    allocator: Allocator,
+    op: AllocOp,
+    name: &'static str,
+    title: &'static str,
+}
+
+impl Allocation {
+    fn new(allocator: Allocator, op: AllocOp) -> Allocation {
+        // e.g. alloc_jemalloc_realloc_growth.
+        let name = format!("alloc_{}_{}", allocator.as_str(), op.as_str());
+        Allocation {
+            allocator,
+            op,
+            name: Box::leak(name.into_boxed_str()),
+            title: Box::leak(alloc_title(allocator, op).into_boxed_str()),
+        }
+    }
+}
+
+impl Benchmark for Allocation {
+    type State = AllocationState;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn description(&self) -> &'static str {
+        match self.op {
+            AllocOp::Small | AllocOp::Medium | AllocOp::Large => {
+                "Allocates 64 blocks straight from the allocator, then frees them."
+            }
+            AllocOp::ReallocGrowth => "Grows a 16 B block to 1 MiB by doubling it with realloc.",
+            AllocOp::CrossThreadFree => "Allocates 64 B blocks in batches another thread frees.",
+        }
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        match self.op {
+            AllocOp::CrossThreadFree => &["allocator", "threaded"],
+            _ => &["allocator"],
+        }
+    }
+
+    fn memory_footprint(&self) -> usize {
+        match self.op {
+            AllocOp::ReallocGrowth => REALLOC_TO,
+            op => op.size() * ALLOCATIONS,
+        }
+    }
+
+    fn threaded(&self) -> bool {
+        matches!(self.op, AllocOp::CrossThreadFree)
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(match self.op {
+            AllocOp::CrossThreadFree => {
+                AllocationState::CrossThread(CrossThreadFree::new(self.allocator))
+            }
+            _ => AllocationState::Blocks(Vec::with_capacity(ALLOCATIONS)),
+        })
+    }
+
+    fn iteration(&self, state: &mut Self::State) -> Control {
+        match (self.op, state) {
+            (AllocOp::ReallocGrowth, _) => unsafe {
+                let mut size = REALLOC_FROM;
+                let mut block = self.allocator.alloc(layout(size));
+                while size < REALLOC_TO {
+                    block = self.allocator.realloc(block, layout(size), size * 2);
+                    size *= 2;
+                }
+                self.allocator.dealloc(block, layout(size));
+            },
+            (AllocOp::CrossThreadFree, AllocationState::CrossThread(cross)) => {
+                let mut batch = cross.freed.recv().unwrap();
+                for _ in 0..ALLOCATIONS {
+                    batch.push(unsafe { self.allocator.alloc(layout(SMALL)) } as usize);
+                }
+                cross.to_free.as_ref().unwrap().send(batch).unwrap();
+            }
+            (op, AllocationState::Blocks(blocks)) => alloc_free(self.allocator, blocks, op.size()),
+            (_, AllocationState::CrossThread(_)) => unreachable!(),
+        }
+        Control::Continue
+    }
+}
+
+pub struct ThreadBlocks {
+    allocator: Allocator,
+    blocks: Vec<*mut u8>,
+}
+
+fn alloc_free_rounds(slice: &mut ThreadBlocks) {
+    for _ in 0..THREADED_ROUNDS {
+        alloc_free(slice.allocator, &mut slice.blocks, SMALL);
+    }
+}
+
+pub struct AllocationThreaded {
+    allocator: Allocator,
+    cores: Vec<Option<CoreId>>,
+    name: &'static str,
+    title: &'static str,
+}
+
+impl AllocationThreaded {
+    fn new(allocator: Allocator, cores: Vec<Option<CoreId>>) -> AllocationThreaded {
+        // e.g. alloc_system_small_4t.
+        let name = format!("alloc_{}_small_{}t", allocator.as_str(), cores.len());
+        let title = alloc_threaded_title(allocator, cores.len());
+        AllocationThreaded {
+            allocator,
+            cores,
+            name: Box::leak(name.into_boxed_str()),
+            title: Box::leak(title.into_boxed_str()),
+        }
+    }
+}
+
+impl Benchmark for AllocationThreaded {
+    type State = Pool;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn description(&self) -> &'static str {
+        "Pinned threads each allocate and free 64 B blocks, 4096 per iteration."
+    }
+
+    fn category(&self) -> Category {
+        Category::Concurrency
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["allocator", "threaded"]
+    }
+
+    fn sampling(&self) -> Sampling {
+        Sampling::PerIteration
+    }
+
+    fn threaded(&self) -> bool {
+        true
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let allocator = self.allocator;
+        Pool::new(
+            &self.cores,
+            move |_| {
+                Ok(ThreadBlocks {
+                    allocator,
+                    blocks: Vec::with_capacity(ALLOCATIONS),
+                })
+            },
+            alloc_free_rounds,
+        )
+    }
+
+    fn iteration(&self, pool: &mut Self::State) -> Control {
+        pool.run();
+        Control::Continue
+    }
+}
@@ -0,0 +1,374 @@
+// How memory bandwidth scales with the number of cores hammering it. Every workload runs with 1 up
+// to every core, each thread pinned to its own core and working on its own slice of the working
+// set. `report scaling` turns the results into a curve and finds where it flattens out.
+
+use super::{Benchmark, Category, Registry};
+use crate::{black_box, Control, Sampling};
+use core_affinity::CoreId;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use std::any::Any;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+// Split between the threads, so an iteration moves the same number of bytes at every thread count.
+pub(super) const WORKING_SET: usize = n_gib_bytes!(1) as usize;
+
+pub fn register(registry: &mut Registry) {
+    let cores = cores();
+    for workload in WORKLOADS.iter() {
+        for threads in 1..=cores.len() {
+            registry.register(MemoryBandwidth::new(*workload, cores[..threads].to_vec()));
+        }
+    }
+}
+
+// Without core ids we still run, just without pinning.
+pub(super) fn cores() -> Vec<Option<CoreId>> {
+    match core_affinity::get_core_ids() {
+        Some(cores) if !cores.is_empty() => cores.into_iter().map(Some).collect(),
+        _ => vec![None; thread::available_parallelism().map_or(1, |n| n.get())],
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub(super) fn cpu_set(cpu: usize) -> libc::cpu_set_t {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    unsafe { libc::CPU_SET(cpu, &mut set) };
+    set
+}
+
+#[cfg(target_os = "linux")]
+pub(super) fn set_affinity(set: &libc::cpu_set_t) {
+    unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), set) };
+}
+
+// The benchmark's thread is pinned to `cpu` for as long as this lives, and gets its old affinity
+// back on drop so the tests after this one aren't stuck on one core. Only Linux lets us read the
+// old affinity, elsewhere the thread stays unpinned.
+pub(super) struct Pinned {
+    #[cfg(target_os = "linux")]
+    affinity: libc::cpu_set_t,
+}
+
+impl Pinned {
+    #[cfg(target_os = "linux")]
+    pub(super) fn new(cpu: usize) -> Pinned {
+        let mut affinity: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        unsafe {
+            libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut affinity)
+        };
+        set_affinity(&cpu_set(cpu));
+        Pinned { affinity }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(super) fn new(_cpu: usize) -> Pinned {
+        Pinned {}
+    }
+}
+
+impl Drop for Pinned {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        set_affinity(&self.affinity);
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Workload {
+    ReadSequential,
+    WriteSequential,
+    ReadRandom,
+}
+
+pub const WORKLOADS: [Workload; 3] = [
+    Workload::ReadSequential,
+    Workload::WriteSequential,
+    Workload::ReadRandom,
+];
+
+impl Workload {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Workload::ReadSequential => "read_sequential",
+            Workload::WriteSequential => "write_sequential",
+            Workload::ReadRandom => "read_random",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Workload::ReadSequential => "Sequential Read Bandwidth",
+            Workload::WriteSequential => "Sequential Write Bandwidth",
+            Workload::ReadRandom => "Random Read Bandwidth",
+        }
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        match self {
+            Workload::ReadSequential => &["threaded", "bandwidth", "sweep", "sequential", "read"],
+            Workload::WriteSequential => &["threaded", "bandwidth", "sweep", "sequential", "write"],
+            Workload::ReadRandom => &["threaded", "bandwidth", "sweep", "random", "read"],
+        }
+    }
+}
+
+pub fn bandwidth_title(workload: Workload, threads: usize) -> String {
+    let unit = if threads == 1 { "thread" } else { "threads" };
+    format!("{}, {} {}", workload.title(), threads, unit)
+}
+
+// Threads that each own a slice of the working set and go through it once per `run`. Each thread
+// allocates and first-touches its own slice after pinning itself, so the memory is local to it
+// unless `setup` puts it elsewhere.
+pub struct Pool {
+    start: Arc<Barrier>,
+    end: Arc<Barrier>,
+    stop: Arc<AtomicBool>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl Pool {
+    // Fails with the first error, or panic, out of any thread's `setup` instead of leaving the
+    // caller waiting for a thread that will never be ready. The pool is stopped and joined before
+    // that.
+    pub(super) fn new<T, S>(
+        cores: &[Option<CoreId>],
+        setup: S,
+        work: fn(&mut T),
+    ) -> io::Result<Pool>
+    where
+        T: 'static,
+        S: Fn(usize) -> io::Result<T> + Clone + Send + 'static,
+    {
+        let start = Arc::new(Barrier::new(cores.len() + 1));
+        let end = Arc::new(Barrier::new(cores.len() + 1));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (ready, set_up) = channel::<io::Result<()>>();
+        let bytes_per_thread = WORKING_SET / cores.len();
+
+        let threads = cores
+            .iter()
+            .copied()
+            .map(|core| {
+                let (start, end, stop, ready, setup) = (
+                    start.clone(),
+                    end.clone(),
+                    stop.clone(),
+                    ready.clone(),
+                    setup.clone(),
+                );
+                thread::spawn(move || {
+                    if let Some(core) = core {
+                        core_affinity::set_for_current(core);
+                    }
+                    // A thread that failed still waits at the barrier with the others, so stopping
+                    // the pool doesn't wait on it forever.
+                    let state = panic::catch_unwind(AssertUnwindSafe(|| setup(bytes_per_thread)))
+                        .unwrap_or_else(|panic| Err(panicked(panic)));
+                    let mut state = match state {
+                        Ok(state) => {
+                            ready.send(Ok(())).unwrap();
+                            Some(state)
+                        }
+                        Err(err) => {
+                            ready.send(Err(err)).unwrap();
+                            None
+                        }
+                    };
+
+                    loop {
+                        start.wait();
+                        if stop.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        if let Some(state) = state.as_mut() {
+                            work(state);
+                        }
+                        end.wait();
+                    }
+                })
+            })
+            .collect();
+
+        let pool = Pool {
+            start,
+            end,
+            stop,
+            threads,
+        };
+        for _ in cores {
+            // Dropping the pool stops the threads.
+            set_up.recv().unwrap()?;
+        }
+        Ok(pool)
+    }
+
+    pub(super) fn run(&self) {
+        self.start.wait();
+        self.end.wait();
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.start.wait();
+        for thread in self.threads.drain(..) {
+            thread.join().unwrap();
+        }
+    }
+}
+
+// A panic in a pool thread's `setup`, as the error `Pool::new` returns for it.
+fn panicked(panic: Box<dyn Any + Send>) -> io::Error {
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_default();
+    io::Error::other(format!("pool thread panicked in setup: {}", message))
+}
+
+pub struct SequentialSlice {
+    pub(super) vec: Vec<u64>,
+}
+
+pub(super) fn sequential_setup(bytes: usize) -> SequentialSlice {
+    SequentialSlice {
+        vec: (0..(bytes / 8) as u64).collect(),
+    }
+}
+
+pub(super) fn read_sequential(slice: &mut SequentialSlice) {
+    black_box(
+        slice
+            .vec
+            .iter()
+            .fold(0u64, |total, x| total.wrapping_add(*x)),
+    );
+}
+
+fn write_sequential(slice: &mut SequentialSlice) {
+    let value = slice.vec[0].wrapping_add(1);
+    slice.vec.fill(value);
+    black_box(slice.vec.as_ptr());
+}
+
+pub struct RandomReadSlice {
+    pub(super) vec: Vec<[u64; 8]>,
+    order: Vec<usize>,
+}
+
+pub(super) fn random_setup(bytes: usize) -> RandomReadSlice {
+    let size_in_elements = bytes / 64;
+    let vec = vec![[1, 2, 3, 4, 5, 6, 7, 8]; size_in_elements];
+    let mut order: Vec<usize> = (0..size_in_elements).collect();
+    order.shuffle(&mut thread_rng());
+    RandomReadSlice { vec, order }
+}
+
+pub(super) fn read_random(slice: &mut RandomReadSlice) {
+    for i in slice.order.iter() {
+        black_box(slice.vec[*i]);
+    }
+}
+
+pub struct MemoryBandwidth {
+    workload: Workload,
+    cores: Vec<Option<CoreId>>,
+    name: &'static str,
+    title: &'static str,
+}
+
+impl MemoryBandwidth {
+    fn new(workload: Workload, cores: Vec<Option<CoreId>>) -> MemoryBandwidth {
+        // e.g. memory_bandwidth_read_sequential_4t. Registered once and kept for the whole run, so
+        // leaking is what `&'static` asks for.
+        let name = format!("memory_bandwidth_{}_{}t", workload.as_str(), cores.len());
+        let title = bandwidth_title(workload, cores.len());
+        MemoryBandwidth {
+            workload,
+            cores,
+            name: Box::leak(name.into_boxed_str()),
+            title: Box::leak(title.into_boxed_str()),
+        }
+    }
+}
+
+impl Benchmark for MemoryBandwidth {
+    type State = Pool;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn description(&self) -> &'static str {
+        "One point on the curve behind `report scaling`, pinned threads share a 1 GiB working set."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        self.workload.tags()
+    }
+
+    // Every thread goes through its slice once per iteration.
+    fn bytes_per_iteration(&self) -> usize {
+        let element = match self.workload {
+            Workload::ReadRandom => 64,
+            _ => 8,
+        };
+        let per_thread = WORKING_SET / self.cores.len();
+        per_thread / element * element * self.cores.len()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        match self.workload {
+            Workload::ReadRandom => WORKING_SET + WORKING_SET / 64 * std::mem::size_of::<usize>(),
+            _ => WORKING_SET,
+        }
+    }
+
+    fn sampling(&self) -> Sampling {
+        Sampling::PerIteration
+    }
+
+    fn threaded(&self) -> bool {
+        true
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        match self.workload {
+            Workload::ReadSequential => Pool::new(
+                &self.cores,
+                |bytes| Ok(sequential_setup(bytes)),
+                read_sequential,
+            ),
+            Workload::WriteSequential => Pool::new(
+                &self.cores,
+                |bytes| Ok(sequential_setup(bytes)),
+                write_sequential,
+            ),
+            Workload::ReadRandom => {
+                Pool::new(&self.cores, |bytes| Ok(random_setup(bytes)), read_random)
+            }
+        }
+    }
+
+    fn iteration(&self, pool: &mut Self::State) -> Control {
+        pool.run();
+        Control::Continue
+    }
+}
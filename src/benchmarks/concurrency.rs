@@ -0,0 +1,557 @@
+// The mutex, and under it what makes a contended mutex slow: atomics on a cache line that other
+// cores want too, with the line shared outright, falsely shared or padded apart, and how long the
+// line takes to get from one core to another, for every pair of cores. `NAPKIN_PING_PONG_SAMPLED=1`
+// only measures the first core against its SMT sibling, another core on its socket and a core on
+// another socket.
+
+use super::bandwidth::{cores, Pinned};
+use super::{Benchmark, Category, Registry, Requirement};
+use crate::{Control, Sampling};
+use core_affinity::CoreId;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+
+pub fn register(registry: &mut Registry) {
+    registry.register(MutexContended);
+    for op in ATOMIC_OPS.iter() {
+        for sharing in SHARINGS.iter() {
+            registry.register(AtomicContended::new(*op, *sharing));
+        }
+    }
+
+    // Without core ids there's nothing to pin to, so no ping-pong. Latency is symmetric enough that
+    // one direction per pair will do.
+    let cores: Vec<CoreId> = cores().into_iter().flatten().collect();
+    let sampled = matches!(
+        std::env::var("NAPKIN_PING_PONG_SAMPLED").as_deref(),
+        Ok("1") | Ok("true")
+    );
+    let pairs = if sampled {
+        sampled_pairs(&cores)
+    } else {
+        cores
+            .iter()
+            .enumerate()
+            .flat_map(|(i, first)| cores[i + 1..].iter().map(move |second| (*first, *second)))
+            .collect()
+    };
+    for (first, second) in pairs {
+        registry.register(CorePingPong::new(first, second));
+    }
+}
+
+// How far apart two cores are, which is most of what the latency between them comes down to.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Distance {
+    SmtSibling,
+    SameSocket,
+    CrossSocket,
+}
+
+pub const DISTANCES: [Distance; 3] = [
+    Distance::SmtSibling,
+    Distance::SameSocket,
+    Distance::CrossSocket,
+];
+
+impl Distance {
+    pub fn title(&self) -> &'static str {
+        match self {
+            Distance::SmtSibling => "SMT sibling",
+            Distance::SameSocket => "same socket",
+            Distance::CrossSocket => "other socket",
+        }
+    }
+}
+
+// The package and core the kernel puts a CPU on. None where there's no sysfs to ask.
+#[cfg(target_os = "linux")]
+fn topology(cpu: usize) -> Option<(usize, usize)> {
+    let read = |file: &str| -> Option<usize> {
+        let path = format!("/sys/devices/system/cpu/cpu{}/topology/{}", cpu, file);
+        std::fs::read_to_string(path).ok()?.trim().parse().ok()
+    };
+    Some((read("physical_package_id")?, read("core_id")?))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn topology(_cpu: usize) -> Option<(usize, usize)> {
+    None
+}
+
+pub fn core_distance(first: usize, second: usize) -> Option<Distance> {
+    let (first, second) = (topology(first)?, topology(second)?);
+    Some(if first.0 != second.0 {
+        Distance::CrossSocket
+    } else if first.1 == second.1 {
+        Distance::SmtSibling
+    } else {
+        Distance::SameSocket
+    })
+}
+
+// The first core against the first core at each distance from it, a handful of pairs where every
+// pair would be quadratic in the core count. Without a topology, just the first two cores.
+fn sampled_pairs(cores: &[CoreId]) -> Vec<(CoreId, CoreId)> {
+    let (first, others) = match cores.split_first() {
+        Some(split) => split,
+        None => return Vec::new(),
+    };
+    let mut pairs: Vec<(CoreId, CoreId)> = DISTANCES
+        .iter()
+        .filter_map(|distance| {
+            others
+                .iter()
+                .find(|other| core_distance(first.id, other.id) == Some(*distance))
+                .map(|other| (*first, *other))
+        })
+        .collect();
+    if pairs.is_empty() {
+        pairs.extend(others.first().map(|other| (*first, *other)));
+    }
+    pairs
+}
+
+pub struct MutexContended;
+
+// The mutex and the thread hammering it, which stops and is joined on drop.
+pub struct MutexTest {
+    mutex: Arc<Mutex<u64>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Drop for MutexTest {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap();
+        }
+    }
+}
+
+impl Benchmark for MutexContended {
+    type State = MutexTest;
+
+    fn name(&self) -> &'static str {
+        "mutex"
+    }
+
+    fn title(&self) -> &'static str {
+        "Mutex"
+    }
+
+    fn description(&self) -> &'static str {
+        "Takes a mutex that another thread is hammering in a loop."
+    }
+
+    fn category(&self) -> Category {
+        Category::Concurrency
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["threaded", "lock"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        1
+    }
+
+    fn threaded(&self) -> bool {
+        true
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let mutex = Arc::new(Mutex::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (t_mutex, t_stop) = (mutex.clone(), stop.clone());
+        let thread = thread::spawn(move || {
+            while !t_stop.load(Ordering::Relaxed) {
+                let mut data = t_mutex.lock().unwrap();
+                // let duration = time::Duration::from_micros(10);
+                // thread::sleep(duration);
+                *data += 10;
+            }
+        });
+
+        Ok(MutexTest {
+            mutex,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        let mut data = test.mutex.lock().unwrap();
+        *data += 10;
+        Control::Continue
+    }
+}
+
+// Up to this many threads hammer the counters, one per core: enough to share one cache line
+// without two of them touching the same counter when it's falsely shared.
+const CONTENDING_THREADS: usize = 8;
+
+#[derive(Clone, Copy)]
+pub enum AtomicOp {
+    FetchAdd,
+    // A load and a `compare_exchange_weak` until it goes through, what lock-free code does.
+    CompareExchange,
+}
+
+pub const ATOMIC_OPS: [AtomicOp; 2] = [AtomicOp::FetchAdd, AtomicOp::CompareExchange];
+
+impl AtomicOp {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AtomicOp::FetchAdd => "fetch_add",
+            AtomicOp::CompareExchange => "cas",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            AtomicOp::FetchAdd => "fetch_add",
+            AtomicOp::CompareExchange => "compare_exchange loop",
+        }
+    }
+
+    #[inline(always)]
+    fn apply(&self, counter: &AtomicU64) {
+        match self {
+            AtomicOp::FetchAdd => {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+            AtomicOp::CompareExchange => {
+                let mut current = counter.load(Ordering::Relaxed);
+                while let Err(actual) = counter.compare_exchange_weak(
+                    current,
+                    current + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    current = actual;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Sharing {
+    // Every thread on the same counter.
+    Shared,
+    // A counter per thread, all of them in one cache line.
+    FalseShared,
+    // A counter per thread, each on its own cache line.
+    Padded,
+}
+
+pub const SHARINGS: [Sharing; 3] = [Sharing::Shared, Sharing::FalseShared, Sharing::Padded];
+
+impl Sharing {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Sharing::Shared => "shared",
+            Sharing::FalseShared => "false_shared",
+            Sharing::Padded => "padded",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Sharing::Shared => "shared counter",
+            Sharing::FalseShared => "false-shared line",
+            Sharing::Padded => "padded lines",
+        }
+    }
+}
+
+pub fn atomic_title(op: AtomicOp, sharing: Sharing) -> String {
+    format!("Atomic {}, {}", op.title(), sharing.title())
+}
+
+#[repr(align(64))]
+#[derive(Default)]
+struct Line([AtomicU64; 8]);
+
+// A line per thread, of which `Shared` and `FalseShared` only use the first.
+struct Counters {
+    lines: Vec<Line>,
+    sharing: Sharing,
+}
+
+impl Counters {
+    fn get(&self, thread: usize) -> &AtomicU64 {
+        match self.sharing {
+            Sharing::Shared => &self.lines[0].0[0],
+            Sharing::FalseShared => &self.lines[0].0[thread],
+            Sharing::Padded => &self.lines[thread].0[0],
+        }
+    }
+}
+
+// Threads pinned to every core but the first, each applying the op to its own counter until
+// dropped. The iteration is the same op from the benchmark's thread, pinned to the first core so
+// it competes for the line with all of them rather than for a core with one of them.
+pub struct Contention {
+    counters: Arc<Counters>,
+    stop: Arc<AtomicBool>,
+    threads: Vec<thread::JoinHandle<()>>,
+    // Dropped after the threads have stopped.
+    _pinned: Option<Pinned>,
+}
+
+impl Drop for Contention {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            thread.join().unwrap();
+        }
+    }
+}
+
+pub struct AtomicContended {
+    op: AtomicOp,
+    sharing: Sharing,
+    name: &'static str,
+    title: &'static str,
+}
+
+impl AtomicContended {
+    fn new(op: AtomicOp, sharing: Sharing) -> AtomicContended {
+        // e.g. atomic_fetch_add_false_shared.
+        let name = format!("atomic_{}_{}", op.as_str(), sharing.as_str());
+        AtomicContended {
+            op,
+            sharing,
+            name: Box::leak(name.into_boxed_str()),
+            title: Box::leak(atomic_title(op, sharing).into_boxed_str()),
+        }
+    }
+}
+
+impl Benchmark for AtomicContended {
+    type State = Contention;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn description(&self) -> &'static str {
+        match self.sharing {
+            Sharing::Shared => "An atomic op on a counter that up to 7 pinned threads hit too.",
+            Sharing::FalseShared => {
+                "An atomic op on our own counter, in the cache line of up to 7 pinned threads' counters."
+            }
+            Sharing::Padded => {
+                "An atomic op on our own counter while up to 7 pinned threads hit theirs, a line each."
+            }
+        }
+    }
+
+    fn category(&self) -> Category {
+        Category::Concurrency
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["threaded", "atomic", "contention"]
+    }
+
+    fn requirements(&self) -> &'static [Requirement] {
+        &[Requirement::MultipleCores]
+    }
+
+    fn threaded(&self) -> bool {
+        true
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let counters = Arc::new(Counters {
+            lines: (0..CONTENDING_THREADS).map(|_| Line::default()).collect(),
+            sharing: self.sharing,
+        });
+        let stop = Arc::new(AtomicBool::new(false));
+        let op = self.op;
+        let cores = cores();
+        let pinned = cores[0].map(|core| Pinned::new(core.id));
+
+        let threads = cores
+            .into_iter()
+            .take(CONTENDING_THREADS)
+            .enumerate()
+            .skip(1)
+            .map(|(index, core)| {
+                let (counters, stop) = (counters.clone(), stop.clone());
+                thread::spawn(move || {
+                    if let Some(core) = core {
+                        core_affinity::set_for_current(core);
+                    }
+                    let counter = counters.get(index);
+                    while !stop.load(Ordering::Relaxed) {
+                        op.apply(counter);
+                    }
+                })
+            })
+            .collect();
+
+        Ok(Contention {
+            counters,
+            stop,
+            threads,
+            _pinned: pinned,
+        })
+    }
+
+    fn iteration(&self, contention: &mut Self::State) -> Control {
+        self.op.apply(contention.counters.get(0));
+        Control::Continue
+    }
+}
+
+// Round trips per iteration of the ping-pong, so the barriers that start and end it are noise.
+pub const PING_PONG_ROUND_TRIPS: usize = 1000;
+
+pub fn ping_pong_title(first: usize, second: usize) -> String {
+    format!("Core-to-core ping-pong, cores {} and {}", first, second)
+}
+
+// One thread pinned to each of two cores bouncing a counter between them: the first bumps it to
+// an odd number and spins until the second has bumped it back to even. Every round trip moves the
+// line across twice, so half of one is the core-to-core latency.
+pub struct PingPong {
+    start: Arc<Barrier>,
+    end: Arc<Barrier>,
+    stop: Arc<AtomicBool>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl PingPong {
+    fn new(first: CoreId, second: CoreId) -> PingPong {
+        let line = Arc::new(Line::default());
+        let start = Arc::new(Barrier::new(2));
+        let end = Arc::new(Barrier::new(2));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let ping = {
+            let (line, start, end, stop) = (line.clone(), start.clone(), end.clone(), stop.clone());
+            thread::spawn(move || {
+                core_affinity::set_for_current(first);
+                let counter = &line.0[0];
+                let mut next = 0;
+                loop {
+                    start.wait();
+                    if stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    for _ in 0..PING_PONG_ROUND_TRIPS {
+                        counter.store(next + 1, Ordering::Release);
+                        while counter.load(Ordering::Acquire) != next + 2 {
+                            std::hint::spin_loop();
+                        }
+                        next += 2;
+                    }
+                    end.wait();
+                }
+            })
+        };
+        let pong = {
+            let (line, stop) = (line, stop.clone());
+            thread::spawn(move || {
+                core_affinity::set_for_current(second);
+                let counter = &line.0[0];
+                while !stop.load(Ordering::Relaxed) {
+                    let value = counter.load(Ordering::Acquire);
+                    if value % 2 == 1 {
+                        counter.store(value + 1, Ordering::Release);
+                    }
+                }
+            })
+        };
+
+        PingPong {
+            start,
+            end,
+            stop,
+            threads: vec![ping, pong],
+        }
+    }
+}
+
+impl Drop for PingPong {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.start.wait();
+        for thread in self.threads.drain(..) {
+            thread.join().unwrap();
+        }
+    }
+}
+
+pub struct CorePingPong {
+    first: CoreId,
+    second: CoreId,
+    name: &'static str,
+    title: &'static str,
+}
+
+impl CorePingPong {
+    fn new(first: CoreId, second: CoreId) -> CorePingPong {
+        // e.g. core_ping_pong_0_3.
+        let name = format!("core_ping_pong_{}_{}", first.id, second.id);
+        CorePingPong {
+            first,
+            second,
+            name: Box::leak(name.into_boxed_str()),
+            title: Box::leak(ping_pong_title(first.id, second.id).into_boxed_str()),
+        }
+    }
+}
+
+impl Benchmark for CorePingPong {
+    type State = PingPong;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn description(&self) -> &'static str {
+        "1000 round trips of a cache line between two pinned cores."
+    }
+
+    fn category(&self) -> Category {
+        Category::Concurrency
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["threaded", "atomic", "latency", "ping-pong"]
+    }
+
+    fn sampling(&self) -> Sampling {
+        Sampling::PerIteration
+    }
+
+    fn threaded(&self) -> bool {
+        true
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(PingPong::new(self.first, self.second))
+    }
+
+    fn iteration(&self, ping_pong: &mut Self::State) -> Control {
+        ping_pong.start.wait();
+        ping_pong.end.wait();
+        Control::Continue
+    }
+}
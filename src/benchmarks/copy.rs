@@ -0,0 +1,328 @@
+// How fast we can copy a buffer: `memcpy` (`copy_from_slice`) from in-cache sizes to multi-GiB, and
+// next to it a copy with AVX2 regular stores and one with AVX2 non-temporal (streaming) stores,
+// which skip the cache and the read-for-ownership of the destination. The 1 GiB copy also runs on
+// every core at once through the bandwidth sweep's `Pool`. `report copy` puts them side by side.
+
+use super::bandwidth::{cores, Pool, WORKING_SET};
+use super::memory::sweep_name;
+use super::{Benchmark, Category, Registry, Requirement};
+use crate::report::format_size;
+use crate::{black_box, Control, Sampling};
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+use std::io;
+
+pub fn register(registry: &mut Registry) {
+    for kernel in COPY_KERNELS.iter() {
+        for size in copy_sweep() {
+            registry.register(MemoryCopy::new(*kernel, size));
+        }
+    }
+
+    // One pinned thread against every core, the curve in between is what `report scaling` is for.
+    let cores = cores();
+    let mut thread_counts = vec![1];
+    if cores.len() > 1 {
+        thread_counts.push(cores.len());
+    }
+    for kernel in COPY_KERNELS.iter() {
+        for threads in thread_counts.iter() {
+            registry.register(MemoryCopyThreaded::new(*kernel, cores[..*threads].to_vec()));
+        }
+    }
+}
+
+// 4 KiB to 4 GiB in powers of four, from L1 to well past the last level of cache.
+pub fn copy_sweep() -> impl Iterator<Item = usize> {
+    (12..=32).step_by(2).map(|shift| 1 << shift)
+}
+
+#[derive(Clone, Copy)]
+pub enum CopyKernel {
+    Memcpy,
+    Stores,
+    StreamingStores,
+}
+
+pub const COPY_KERNELS: [CopyKernel; 3] = [
+    CopyKernel::Memcpy,
+    CopyKernel::Stores,
+    CopyKernel::StreamingStores,
+];
+
+impl CopyKernel {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CopyKernel::Memcpy => "memcpy",
+            CopyKernel::Stores => "stores",
+            CopyKernel::StreamingStores => "stream",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            CopyKernel::Memcpy => "memcpy",
+            CopyKernel::Stores => "AVX2 stores",
+            CopyKernel::StreamingStores => "AVX2 streaming stores",
+        }
+    }
+
+    fn requirements(&self) -> &'static [Requirement] {
+        match self {
+            CopyKernel::Memcpy => &[],
+            _ => &[Requirement::Avx2],
+        }
+    }
+
+    fn work(&self) -> fn(&mut CopySlice) {
+        match self {
+            CopyKernel::Memcpy => copy_memcpy,
+            CopyKernel::Stores => copy_stores,
+            CopyKernel::StreamingStores => copy_streaming_stores,
+        }
+    }
+}
+
+pub fn copy_title(kernel: CopyKernel, size: usize) -> String {
+    format!("Copy Vec with {}, {}", kernel.title(), format_size(size))
+}
+
+pub fn copy_threaded_title(kernel: CopyKernel, threads: usize) -> String {
+    let unit = if threads == 1 { "thread" } else { "threads" };
+    format!(
+        "Copy {} with {}, {} {}",
+        format_size(WORKING_SET),
+        kernel.title(),
+        threads,
+        unit
+    )
+}
+
+pub fn avx2() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+// A cache line, so both buffers start on one and the streaming stores get the 32 byte alignment
+// they need.
+#[derive(Clone, Copy)]
+#[repr(C, align(64))]
+pub struct Line([u64; 8]);
+
+pub struct CopySlice {
+    src: Vec<Line>,
+    dst: Vec<Line>,
+}
+
+// Both buffers are written here, so neither faults its pages in while we measure.
+fn copy_setup(bytes: usize) -> CopySlice {
+    let size_in_elements = bytes / 64;
+    CopySlice {
+        src: vec![Line([1, 2, 3, 4, 5, 6, 7, 8]); size_in_elements],
+        dst: vec![Line([8, 7, 6, 5, 4, 3, 2, 1]); size_in_elements],
+    }
+}
+
+fn copy_memcpy(slice: &mut CopySlice) {
+    slice.dst.copy_from_slice(&slice.src);
+    black_box(slice.dst.as_ptr());
+}
+
+fn copy_stores(slice: &mut CopySlice) {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        copy_stores_avx2(&mut slice.dst, &slice.src)
+    };
+    #[cfg(not(target_arch = "x86_64"))]
+    unreachable!("AVX2 stores need x86_64");
+    black_box(slice.dst.as_ptr());
+}
+
+fn copy_streaming_stores(slice: &mut CopySlice) {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        copy_streaming_stores_avx2(&mut slice.dst, &slice.src)
+    };
+    #[cfg(not(target_arch = "x86_64"))]
+    unreachable!("AVX2 streaming stores need x86_64");
+    black_box(slice.dst.as_ptr());
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn copy_stores_avx2(dst: &mut [Line], src: &[Line]) {
+    for (dst, src) in dst.iter_mut().zip(src.iter()) {
+        let src = src.0.as_ptr() as *const __m256i;
+        let dst = dst.0.as_mut_ptr() as *mut __m256i;
+        _mm256_store_si256(dst, _mm256_load_si256(src));
+        _mm256_store_si256(dst.add(1), _mm256_load_si256(src.add(1)));
+    }
+}
+
+// The stores go to write-combining buffers rather than the cache, the fence makes them visible
+// before we call the copy done.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn copy_streaming_stores_avx2(dst: &mut [Line], src: &[Line]) {
+    for (dst, src) in dst.iter_mut().zip(src.iter()) {
+        let src = src.0.as_ptr() as *const __m256i;
+        let dst = dst.0.as_mut_ptr() as *mut __m256i;
+        _mm256_stream_si256(dst, _mm256_load_si256(src));
+        _mm256_stream_si256(dst.add(1), _mm256_load_si256(src.add(1)));
+    }
+    _mm_sfence();
+}
+
+// One step of the size sweep, a single unpinned thread copying `size` bytes per iteration.
+pub struct MemoryCopy {
+    kernel: CopyKernel,
+    size: usize,
+    name: &'static str,
+    title: &'static str,
+}
+
+impl MemoryCopy {
+    fn new(kernel: CopyKernel, size: usize) -> MemoryCopy {
+        MemoryCopy {
+            kernel,
+            size,
+            name: sweep_name(&format!("memory_copy_{}", kernel.as_str()), size),
+            title: Box::leak(copy_title(kernel, size).into_boxed_str()),
+        }
+    }
+}
+
+impl Benchmark for MemoryCopy {
+    type State = CopySlice;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn description(&self) -> &'static str {
+        match self.kernel {
+            CopyKernel::Memcpy => "One step of the copy sweep behind `report copy`, `memcpy` between two vectors.",
+            CopyKernel::Stores => "One step of the copy sweep behind `report copy`, 32 byte AVX2 loads and stores.",
+            CopyKernel::StreamingStores => "One step of the copy sweep behind `report copy`, AVX2 loads and non-temporal stores.",
+        }
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["copy", "sequential", "read", "write", "sweep"]
+    }
+
+    fn requirements(&self) -> &'static [Requirement] {
+        self.kernel.requirements()
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        self.size
+    }
+
+    fn memory_footprint(&self) -> usize {
+        2 * self.size
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(copy_setup(self.size))
+    }
+
+    fn iteration(&self, slice: &mut Self::State) -> Control {
+        (self.kernel.work())(slice);
+        Control::Continue
+    }
+}
+
+// 1 GiB copied by pinned threads, each copying its own slice.
+pub struct MemoryCopyThreaded {
+    kernel: CopyKernel,
+    cores: Vec<Option<core_affinity::CoreId>>,
+    name: &'static str,
+    title: &'static str,
+}
+
+impl MemoryCopyThreaded {
+    fn new(kernel: CopyKernel, cores: Vec<Option<core_affinity::CoreId>>) -> MemoryCopyThreaded {
+        // e.g. memory_copy_stream_8t.
+        let name = format!("memory_copy_{}_{}t", kernel.as_str(), cores.len());
+        let title = copy_threaded_title(kernel, cores.len());
+        MemoryCopyThreaded {
+            kernel,
+            cores,
+            name: Box::leak(name.into_boxed_str()),
+            title: Box::leak(title.into_boxed_str()),
+        }
+    }
+}
+
+impl Benchmark for MemoryCopyThreaded {
+    type State = Pool;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn description(&self) -> &'static str {
+        "Pinned threads copy their own slice of 1 GiB, an iteration copies all of it."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["copy", "sequential", "read", "write", "threaded"]
+    }
+
+    fn requirements(&self) -> &'static [Requirement] {
+        self.kernel.requirements()
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        let per_thread = WORKING_SET / self.cores.len();
+        per_thread / 64 * 64 * self.cores.len()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        2 * WORKING_SET
+    }
+
+    fn sampling(&self) -> Sampling {
+        Sampling::PerIteration
+    }
+
+    fn threaded(&self) -> bool {
+        true
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Pool::new(
+            &self.cores,
+            |bytes| Ok(copy_setup(bytes)),
+            self.kernel.work(),
+        )
+    }
+
+    fn iteration(&self, pool: &mut Self::State) -> Control {
+        pool.run();
+        Control::Continue
+    }
+}
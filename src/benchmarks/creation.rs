@@ -0,0 +1,235 @@
+// What it costs to get a new thread or process to run something: spawning and joining a thread,
+// handing a task to a pool that already has its threads, fork(2), spawning `true` through
+// `Command` (posix_spawn(3) underneath) and a bare clone(2) with CLONE_VM. An iteration creates
+// one and waits for it to finish, so the latency is the per-creation cost. `report spawn` has them.
+
+use super::bandwidth::cores;
+use super::{Benchmark, Category, Registry, Requirement};
+use crate::{black_box, Control, Sampling};
+use std::io;
+use std::process::Command;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub fn register(registry: &mut Registry) {
+    for creation in CREATIONS.iter() {
+        registry.register(Create(*creation));
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Creation {
+    ThreadSpawn,
+    ThreadPool,
+    Fork,
+    Spawn,
+    // The child shares our memory and runs one empty function on a stack we hand it, about as
+    // little as the kernel can do for a new task.
+    CloneVm,
+}
+
+pub const CREATIONS: [Creation; 5] = [
+    Creation::ThreadSpawn,
+    Creation::ThreadPool,
+    Creation::Fork,
+    Creation::Spawn,
+    Creation::CloneVm,
+];
+
+impl Creation {
+    fn name(&self) -> &'static str {
+        match self {
+            Creation::ThreadSpawn => "thread_spawn",
+            Creation::ThreadPool => "thread_pool_dispatch",
+            Creation::Fork => "process_fork",
+            Creation::Spawn => "process_spawn",
+            Creation::CloneVm => "process_clone_vm",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Creation::ThreadSpawn => "Thread spawn + join",
+            Creation::ThreadPool => "Thread pool task round trip",
+            Creation::Fork => "Process fork + waitpid",
+            Creation::Spawn => "Process spawn `true` + wait",
+            Creation::CloneVm => "clone(CLONE_VM) + waitpid",
+        }
+    }
+}
+
+type Task = Box<dyn FnOnce() + Send>;
+
+// A thread per core pulling tasks off one queue, until the queue is dropped.
+pub struct ThreadPool {
+    tasks: Option<Sender<Task>>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl ThreadPool {
+    fn new(size: usize) -> ThreadPool {
+        let (tasks, queue) = channel::<Task>();
+        let queue = Arc::new(Mutex::new(queue));
+        let threads = (0..size)
+            .map(|_| {
+                let queue = queue.clone();
+                thread::spawn(move || loop {
+                    let task = match queue.lock().unwrap().recv() {
+                        Ok(task) => task,
+                        Err(_) => return,
+                    };
+                    task();
+                })
+            })
+            .collect();
+
+        ThreadPool {
+            tasks: Some(tasks),
+            threads,
+        }
+    }
+
+    fn execute(&self, task: Task) {
+        self.tasks.as_ref().unwrap().send(task).unwrap();
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.tasks.take();
+        for thread in self.threads.drain(..) {
+            thread.join().unwrap();
+        }
+    }
+}
+
+pub struct CreationState {
+    pool: Option<ThreadPool>,
+    // The pool's reply to every task, so we wait for it to have run.
+    done: Option<(Sender<()>, Receiver<()>)>,
+    // The CLONE_VM child's stack.
+    stack: Vec<u8>,
+}
+
+#[cfg(target_os = "linux")]
+extern "C" fn clone_child(_: *mut libc::c_void) -> libc::c_int {
+    0
+}
+
+struct Create(Creation);
+
+impl Benchmark for Create {
+    type State = CreationState;
+
+    fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    fn title(&self) -> &'static str {
+        self.0.title()
+    }
+
+    fn description(&self) -> &'static str {
+        match self.0 {
+            Creation::ThreadSpawn => "Spawns a thread that does nothing and joins it.",
+            Creation::ThreadPool => {
+                "Sends an empty task to a pool of a thread per core and waits for it to run."
+            }
+            Creation::Fork => "fork(2)s a child that exits right away and waits for it.",
+            Creation::Spawn => "Runs `true` through `Command`, posix_spawn(3) where libc has it.",
+            Creation::CloneVm => "clone(2) with CLONE_VM of an empty function, then waitpid(2).",
+        }
+    }
+
+    fn category(&self) -> Category {
+        match self.0 {
+            Creation::ThreadSpawn | Creation::ThreadPool => Category::Concurrency,
+            _ => Category::Syscall,
+        }
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        match self.0 {
+            Creation::ThreadSpawn | Creation::ThreadPool => &["threaded", "spawn"],
+            _ => &["process", "spawn"],
+        }
+    }
+
+    fn requirements(&self) -> &'static [Requirement] {
+        match self.0 {
+            Creation::CloneVm => &[Requirement::Linux],
+            _ => &[],
+        }
+    }
+
+    fn sampling(&self) -> Sampling {
+        Sampling::PerIteration
+    }
+
+    fn threaded(&self) -> bool {
+        matches!(self.0, Creation::ThreadPool)
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(match self.0 {
+            Creation::ThreadPool => CreationState {
+                pool: Some(ThreadPool::new(cores().len())),
+                done: Some(channel()),
+                stack: Vec::new(),
+            },
+            Creation::CloneVm => CreationState {
+                pool: None,
+                done: None,
+                stack: vec![0; 64 * 1024],
+            },
+            _ => CreationState {
+                pool: None,
+                done: None,
+                stack: Vec::new(),
+            },
+        })
+    }
+
+    fn iteration(&self, state: &mut Self::State) -> Control {
+        match self.0 {
+            Creation::ThreadSpawn => thread::spawn(|| black_box(0)).join().map(drop).unwrap(),
+            Creation::ThreadPool => {
+                let (done, finished) = state.done.as_ref().unwrap();
+                let done = done.clone();
+                state
+                    .pool
+                    .as_ref()
+                    .unwrap()
+                    .execute(Box::new(move || done.send(()).unwrap()));
+                finished.recv().unwrap();
+            }
+            Creation::Fork => match unsafe { libc::fork() } {
+                -1 => panic!("fork(2): {}", std::io::Error::last_os_error()),
+                0 => unsafe { libc::_exit(0) },
+                pid => unsafe {
+                    libc::waitpid(pid, std::ptr::null_mut(), 0);
+                },
+            },
+            Creation::Spawn => {
+                black_box(Command::new("true").status().unwrap());
+            }
+            #[cfg(target_os = "linux")]
+            Creation::CloneVm => unsafe {
+                // The stack grows down, so the child gets the end of it.
+                let top = state.stack.as_mut_ptr().add(state.stack.len());
+                let pid = libc::clone(
+                    clone_child,
+                    top as *mut libc::c_void,
+                    libc::CLONE_VM | libc::SIGCHLD,
+                    std::ptr::null_mut(),
+                );
+                assert!(pid > 0, "clone(2): {}", std::io::Error::last_os_error());
+                libc::waitpid(pid, std::ptr::null_mut(), 0);
+            },
+            #[cfg(not(target_os = "linux"))]
+            Creation::CloneVm => unreachable!("clone(2) is Linux only"),
+        }
+        Control::Continue
+    }
+}
@@ -0,0 +1,216 @@
+use super::{Benchmark, Category, Registry, Requirement};
+use crate::{Control, Sampling};
+use mysql::prelude::*;
+use mysql::{params, Opts, Pool};
+use redis::Commands;
+use std::io;
+use std::thread;
+
+pub fn register(registry: &mut Registry) {
+    registry.register(RedisReadSingleKey);
+    registry.register(MysqlWrite);
+}
+
+pub struct RedisReadSingleKey;
+
+impl Benchmark for RedisReadSingleKey {
+    type State = redis::Connection;
+
+    fn name(&self) -> &'static str {
+        "redis_read_single_key"
+    }
+
+    fn title(&self) -> &'static str {
+        "Redis Read"
+    }
+
+    fn description(&self) -> &'static str {
+        "GET of a 64 byte value from a local Redis."
+    }
+
+    fn category(&self) -> Category {
+        Category::Database
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["redis", "loopback"]
+    }
+
+    fn requirements(&self) -> &'static [Requirement] {
+        &[Requirement::Redis]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        64
+    }
+
+    fn sampling(&self) -> Sampling {
+        Sampling::PerIteration
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let mut con = client.get_connection().unwrap();
+        let bytes: Vec<u8> = (0..64).map(|_| rand::random::<u8>()).collect();
+        con.set::<&str, Vec<u8>, ()>("1", bytes).unwrap();
+        Ok(con)
+    }
+
+    fn iteration(&self, con: &mut Self::State) -> Control {
+        std::mem::drop::<Vec<u8>>(con.get("1").unwrap());
+        Control::Continue
+    }
+}
+
+static MYSQL_URL: &str = "mysql://root:@localhost:3306/napkin";
+
+// struct Product {
+//     id: i64,
+//     shop_id: i64,
+//     title: Option<String>,
+//     body_html: Option<String>,
+//     vendor: Option<String>,
+//     created_at: SystemTime,
+//     updated_at: SystemTime,
+// }
+
+// https://mydbops.wordpress.com/2018/07/27/innodb-physical-files-on-mysql-8-0/
+// 0x5 => 5 => ib_logfile0: REDO LOG
+// 0x1C => 28 => binlog: BIN LOG
+// 0x9 => 9 => ibdata: SHARED TABLESPACE
+// 0x20 => 32 => products.ibd: TABLE ITSELF
+// 0xD => 13 => undo
+// 0x19 => temp_5.ibt
+//
+// I think some are slow, some fast, due to filesystem batching?
+// https://www.kernel.org/doc/Documentation/filesystems/ext4.txt
+//
+// This is an `strace` for a MYSQL insert. Notice the huge variability in elapsed time (in
+// microseconds). You can see the 2PC, then the later stages (after the transaction returns) to
+// update InnoDB (table, double-write buffer, etc.)
+//
+// Is the variability due to EXT4 batching? max_batch_time / min_batch_time mounting options
+// (defaults are 0..15ms)
+// https://www.kernel.org/doc/Documentation/filesystems/ext4.txt
+// If we trace the kernel here for a backtrace in the fsync path in ext4
+// (http://www.brendangregg.com/blog/2016-01-18/ebpf-stack-trace-hack.html) we would likely get
+// this.
+//
+// PID/THRD        RELATIVE  ELAPSD    CPU SYSCALL(args)
+// 4020/0x15e84b:  43494372    5536     99 fsync(0x5, 0x0, 0x0)   // PREPARE, SLOW
+// 4020/0xa145e6:    535406     267     93 fsync(0x1C, 0x0, 0x0)  // PREPARE, FAST
+// 4020/0x15e84b:  43494492     238     87 fsync(0x5, 0x0, 0x0)   // ?, FAST
+// 4020/0x15e84b:  43495108    2796    129 fsync(0x5, 0x0, 0x0)   // COMMIT, SLOW
+//
+// I think all this happens after client has returned..?
+//
+// 4020/0x15e7a7:  14185246    5577    147 fsync(0x9, 0x0, 0x0)  // ?
+// 4020/0x15e7a3:     81974     277     92 fsync(0x9, 0x0, 0x0)  // ?
+// 4020/0x15e7a3:     82035     216     52 fsync(0x20, 0x0, 0x0) // FLUSH
+// 4020/0x15e7a3:     82116     231     61 fsync(0xD, 0x0, 0x0)  // ?
+// 4020/0x15e84b:  43495999     249     86 fsync(0x5, 0x0, 0x0)  // ?
+// 4020/0x15e848:  130442427     195     37 fsync(0x5, 0x0, 0x0) // ?
+// 4020/0x15e7a7:  14185565    5626     89 fsync(0x9, 0x0, 0x0)  // ?
+// 4020/0x15e7a3:     82274     359     90 fsync(0x19, 0x0, 0x0) //
+// 4020/0x15e848:  130444415     277    110 fsync(0x5, 0x0, 0x0)
+pub struct MysqlWrite;
+
+impl Benchmark for MysqlWrite {
+    type State = Pool;
+
+    fn name(&self) -> &'static str {
+        "mysql_write"
+    }
+
+    fn title(&self) -> &'static str {
+        "MySQL Write"
+    }
+
+    fn description(&self) -> &'static str {
+        "16 threads each insert 1,000 rows into a fresh `products` table in a local MySQL."
+    }
+
+    fn category(&self) -> Category {
+        Category::Database
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["mysql", "fsync", "threaded"]
+    }
+
+    fn requirements(&self) -> &'static [Requirement] {
+        &[Requirement::MySql]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        8 + 17
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let opts = Opts::from_url(MYSQL_URL).unwrap();
+        let pool = Pool::new(opts).unwrap();
+        let mut conn = pool.get_conn().unwrap();
+        conn.query_drop(
+            r"
+            DROP TABLE IF EXISTS products;
+        ",
+        )
+        .unwrap();
+
+        conn.query_drop(
+            r"
+            CREATE TABLE IF NOT EXISTS `products` (
+              `id` bigint(20) NOT NULL AUTO_INCREMENT,
+              `shop_id` bigint(20) DEFAULT NULL,
+              `title` varchar(255) DEFAULT NULL,
+              `body_html` mediumtext,
+              `vendor` varchar(255) DEFAULT NULL,
+              `created_at` datetime DEFAULT NULL,
+              `updated_at` datetime DEFAULT NULL,
+              PRIMARY KEY (`id`)
+            ) ENGINE=InnoDB AUTO_INCREMENT=0 DEFAULT CHARSET=utf8mb4 ROW_FORMAT=DYNAMIC
+        ",
+        )
+        .unwrap();
+        Ok(pool)
+    }
+
+    fn iteration(&self, pool: &mut Self::State) -> Control {
+        let mut handles = vec![];
+
+        // Why is this faster than fsync(2)?
+        //
+        // (1) Concurrent fsyncs to multiple disks...?
+        // (2) Group Commit?
+        //
+        // For some reason, some of these fsyncs are taking < 1ms, wheras in my benchmarks they
+        // typically take 5ms (which also does happen). Extremely variable.
+        for i in 0..16 {
+            eprintln!("thread: {}", i);
+            handles.push(thread::spawn({
+                let pool = pool.clone();
+                move || {
+                    let mut conn = pool.get_conn().unwrap();
+                    for _ in 0..1000 {
+                        conn.exec_drop(
+                            r"INSERT INTO products (shop_id, title) VALUES (:shop_id, :title)",
+                            params! { "shop_id" => 123, "title" => "aerodynamic chair" },
+                        )
+                        .unwrap();
+                    }
+                }
+            }));
+        }
+        // Expected 'naive' fsyncs to the binlog: 16 * 1,000 => 16,000
+        //
+        // Actual as per `sudo dtruss -e -n mysql -t fsync 2>&1 | grep "fsync(0x1C"`:
+        //
+        // 71 entries!
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        // Start over with an empty table so every sample inserts into the same size of table.
+        Control::ResetWithSetup
+    }
+}
@@ -0,0 +1,488 @@
+#[cfg(target_os = "linux")]
+use super::Requirement;
+use super::{Benchmark, Category, Registry};
+use crate::{benchmark_file_name, black_box, drop_file_page_cache, Control, Sampling};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::process::Command;
+
+#[cfg(target_os = "linux")]
+use std::os::unix::io::*;
+
+pub fn register(registry: &mut Registry) {
+    registry.register(DiskReadSequential);
+    registry.register(DiskReadRandom);
+    registry.register(DiskWriteSequentialNoFsync);
+    #[cfg(target_os = "linux")]
+    registry.register(DiskReadSequentialIoUring);
+    registry.register(DiskWriteSequentialFsync);
+}
+
+pub struct DiskWriteTest {
+    bytes: Vec<u8>,
+    file: std::fs::File,
+    written: usize,
+}
+
+// The no-fsync writes go to the page cache as fast as memory allows, so without a cap the file
+// would grow by gigabytes a second. Past it a `ResetWithSetup` truncates it again.
+const NO_FSYNC_FILE_SIZE: usize = n_gib_bytes!(1) as usize;
+
+fn disk_write_setup(size_of_writes: usize) -> DiskWriteTest {
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(benchmark_file_name())
+        .unwrap();
+
+    let bytes: Vec<u8> = (0..size_of_writes).map(|_| rand::random::<u8>()).collect();
+
+    DiskWriteTest {
+        bytes,
+        file,
+        written: 0,
+    }
+}
+
+pub struct DiskWriteSequentialFsync;
+
+impl Benchmark for DiskWriteSequentialFsync {
+    type State = DiskWriteTest;
+
+    fn name(&self) -> &'static str {
+        "disk_write_sequential_fsync"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sequential Disk Write, Fsync"
+    }
+
+    fn description(&self) -> &'static str {
+        "Appends 8 KiB at a time with fdatasync(2) after every write."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["sequential", "write", "fsync"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        n_kib_bytes!(8) as usize
+    }
+
+    fn sampling(&self) -> Sampling {
+        Sampling::PerIteration
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(disk_write_setup(self.bytes_per_iteration()))
+    }
+
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        test.file.write_all(&test.bytes).unwrap();
+        test.file.sync_data().unwrap();
+        Control::Continue
+    }
+
+    fn teardown(&self) {
+        fs::remove_file(benchmark_file_name()).unwrap();
+    }
+}
+
+pub struct DiskWriteSequentialNoFsync;
+
+impl Benchmark for DiskWriteSequentialNoFsync {
+    type State = DiskWriteTest;
+
+    fn name(&self) -> &'static str {
+        "disk_write_sequential_no_fsync"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sequential Disk Write, No Fsync"
+    }
+
+    fn description(&self) -> &'static str {
+        "Appends 8 KiB at a time without fsync, starting over once the file is 1 GiB."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["sequential", "write"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        n_kib_bytes!(8) as usize
+    }
+
+    fn disk_footprint(&self) -> usize {
+        NO_FSYNC_FILE_SIZE
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(disk_write_setup(self.bytes_per_iteration()))
+    }
+
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        test.file.write_all(&test.bytes).unwrap();
+        test.written += test.bytes.len();
+        if test.written >= NO_FSYNC_FILE_SIZE {
+            return Control::ResetWithSetup;
+        }
+        Control::Continue
+    }
+
+    fn teardown(&self) {
+        fs::remove_file(benchmark_file_name()).unwrap();
+    }
+}
+
+const READ_BUF_SIZE: usize = n_kib_bytes!(8) as usize;
+const SEQUENTIAL_FILE_SIZE: usize = n_gib_bytes!(1) as usize;
+
+// Writing the file takes longer than reading it, so a `ResetWithSetup` after reading it to the end
+// reuses it and only drops it from the page cache again.
+fn sequential_read_file() -> fs::File {
+    let file_name = benchmark_file_name();
+    let exists = fs::metadata(&file_name)
+        .is_ok_and(|metadata| metadata.len() == SEQUENTIAL_FILE_SIZE as u64);
+    if !exists {
+        let buffer = vec![0; SEQUENTIAL_FILE_SIZE];
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .read(true)
+            .open(&file_name)
+            .unwrap();
+        file.write_all(&buffer).unwrap();
+        file.sync_data().unwrap();
+    }
+
+    let file = OpenOptions::new().read(true).open(&file_name).unwrap();
+    drop_file_page_cache(&file);
+
+    #[cfg(target_os = "linux")]
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL);
+    }
+
+    // TODO: for linux
+    if cfg!(target_os = "macos") {
+        Command::new("sudo")
+            .arg("purge")
+            .output()
+            .expect("failed to flush page cache");
+    }
+
+    file
+}
+
+pub struct DiskReadSequential;
+
+pub struct DiskReadSequentialTest {
+    buffer: [u8; READ_BUF_SIZE],
+    file: fs::File,
+}
+
+impl Benchmark for DiskReadSequential {
+    type State = DiskReadSequentialTest;
+
+    fn name(&self) -> &'static str {
+        "disk_read_sequential"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sequential Disk Read"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reads a 1 GiB file 8 KiB at a time after dropping it from the page cache."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["sequential", "read"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        READ_BUF_SIZE
+    }
+
+    fn memory_footprint(&self) -> usize {
+        SEQUENTIAL_FILE_SIZE
+    }
+
+    fn disk_footprint(&self) -> usize {
+        SEQUENTIAL_FILE_SIZE
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(DiskReadSequentialTest {
+            buffer: [0; READ_BUF_SIZE],
+            file: sequential_read_file(),
+        })
+    }
+
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        let n = test.file.read(&mut test.buffer).unwrap();
+        // Reading it again would be reading the page cache.
+        if n < READ_BUF_SIZE {
+            return Control::ResetWithSetup;
+        };
+        Control::Continue
+    }
+
+    fn teardown(&self) {
+        fs::remove_file(benchmark_file_name()).unwrap();
+    }
+}
+
+// https://github.com/axboe/liburing/blob/master/examples/io_uring-cp.c
+#[cfg(target_os = "linux")]
+const IO_URING_BUF_SIZE: usize = n_kib_bytes!(32) as usize;
+#[cfg(target_os = "linux")]
+const IO_URING_READS_PER_ITERATION: isize = 64;
+
+#[cfg(target_os = "linux")]
+pub struct DiskReadSequentialIoUring;
+
+#[cfg(target_os = "linux")]
+pub struct DiskReadSequentialIoUringTest {
+    buffers: Vec<Vec<u8>>,
+    file: fs::File,
+    ring: rio::Rio,
+    size: usize,
+    offset: usize,
+}
+
+// TODO: checksum somehow
+#[cfg(target_os = "linux")]
+impl Benchmark for DiskReadSequentialIoUring {
+    type State = DiskReadSequentialIoUringTest;
+
+    fn name(&self) -> &'static str {
+        "disk_read_sequential_io_uring"
+    }
+
+    fn title(&self) -> &'static str {
+        "Io-uring Sequential Disk Read"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reads a 1 GiB file through io_uring, 64 reads of 32 KiB in flight per iteration."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["sequential", "read", "io_uring"]
+    }
+
+    fn requirements(&self) -> &'static [Requirement] {
+        &[Requirement::Linux, Requirement::IoUring]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        IO_URING_BUF_SIZE * (IO_URING_READS_PER_ITERATION as usize)
+    }
+
+    fn memory_footprint(&self) -> usize {
+        SEQUENTIAL_FILE_SIZE
+    }
+
+    fn disk_footprint(&self) -> usize {
+        SEQUENTIAL_FILE_SIZE
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let file = sequential_read_file();
+        let ring = rio::new().expect("create uring");
+        let buffers = vec![vec![0; IO_URING_BUF_SIZE]; IO_URING_READS_PER_ITERATION as usize];
+        Ok(DiskReadSequentialIoUringTest {
+            buffers,
+            file,
+            ring,
+            size: SEQUENTIAL_FILE_SIZE,
+            offset: 0,
+        })
+    }
+
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        use std::slice;
+
+        let ptr = test.buffers.as_mut_ptr();
+        let mut completions = vec![];
+
+        for i in 0..IO_URING_READS_PER_ITERATION {
+            if test.size == 0 {
+                eprintln!("Stopping early");
+                break;
+            }
+
+            unsafe {
+                let buf = &slice::from_raw_parts_mut(ptr.offset(i), 1)[0];
+                completions.push(test.ring.read_at(&test.file, buf, test.offset as u64));
+            }
+
+            test.offset += IO_URING_BUF_SIZE;
+            test.size -= IO_URING_BUF_SIZE;
+        }
+
+        for completion in completions.into_iter() {
+            let read = completion.wait().unwrap();
+            if read < IO_URING_BUF_SIZE {
+                eprintln!("at end?");
+            }
+        }
+
+        if test.size == 0 {
+            return Control::ResetWithSetup;
+        }
+
+        Control::Continue
+    }
+
+    fn teardown(&self) {
+        let _ = fs::remove_file(benchmark_file_name());
+    }
+}
+
+pub struct DiskReadRandom;
+
+pub struct DiskReadRandomTest {
+    buffer: [u8; READ_BUF_SIZE],
+    pages: Vec<u64>,
+    i: usize,
+    file: std::fs::File,
+}
+
+impl Benchmark for DiskReadRandom {
+    type State = DiskReadRandomTest;
+
+    fn name(&self) -> &'static str {
+        "disk_read_random"
+    }
+
+    fn title(&self) -> &'static str {
+        "Random Disk Seek, No Page Cache"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reads 8 KiB from every page of an 8 GiB file once, in random order, after dropping it from the page cache."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["random", "read"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        READ_BUF_SIZE
+    }
+
+    fn sampling(&self) -> Sampling {
+        Sampling::PerIteration
+    }
+
+    fn memory_footprint(&self) -> usize {
+        n_gib_bytes!(8) as usize
+    }
+
+    fn disk_footprint(&self) -> usize {
+        n_gib_bytes!(8) as usize
+    }
+
+    fn stops_early(&self) -> bool {
+        true
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let file_name = benchmark_file_name();
+        let page_size = page_size::get();
+        std::mem::drop(fs::remove_file(&file_name));
+        let buffer = vec![0; n_gib_bytes!(8) as usize];
+        {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .truncate(true)
+                .write(true)
+                .read(true)
+                .open(&file_name)
+                .unwrap();
+            file.write_all(&buffer).unwrap();
+            file.sync_data().unwrap();
+            drop_file_page_cache(&file);
+        }
+
+        let file = OpenOptions::new().read(true).open(&file_name).unwrap();
+
+        // This is to ensure we only visit each page once. Otherwise this is essentially just
+        // benchmarking syscall + page cache, which is going to be awfully close to random
+        // memory read.
+        let mut pages: Vec<u64> = Vec::new();
+        for i in 0..(buffer.len() / page_size) {
+            pages.push((i * page_size + 1) as u64);
+        }
+        pages.shuffle(&mut thread_rng());
+
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_RANDOM);
+        }
+
+        // TODO: for linux
+        if cfg!(target_os = "macos") {
+            Command::new("sudo")
+                .arg("purge")
+                .output()
+                .expect("failed to flush page cache");
+        }
+
+        let buffer: [u8; READ_BUF_SIZE] = [0; READ_BUF_SIZE];
+
+        Ok(DiskReadRandomTest {
+            file,
+            pages,
+            buffer,
+            i: 0,
+        })
+    }
+
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        test.file.seek(SeekFrom::Start(test.pages[test.i])).unwrap();
+        test.file.read_exact(&mut test.buffer).unwrap();
+        black_box(test.buffer);
+        test.i += 1;
+
+        if test.i == test.pages.len() {
+            return Control::Stop;
+        };
+
+        Control::Continue
+    }
+
+    fn teardown(&self) {
+        fs::remove_file(benchmark_file_name()).unwrap();
+    }
+}
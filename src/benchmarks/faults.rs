@@ -0,0 +1,372 @@
+// What it costs to get memory we haven't touched yet: a minor fault on a fresh anonymous page, a
+// major fault on a file-backed mapping whose pages were dropped from the page cache, mmap(2) and
+// munmap(2) of a region we never touch, and a region faulting back in after madvise(MADV_DONTNEED)
+// handed its pages back. Enough to estimate the startup of a process with a large heap, or an
+// allocator returning memory to the kernel too eagerly. `report faults` has them.
+
+#[cfg(target_os = "linux")]
+use super::memory::sweep_name;
+use super::Registry;
+#[cfg(target_os = "linux")]
+use super::{Benchmark, Category, Requirement};
+use crate::report::format_size;
+#[cfg(target_os = "linux")]
+use crate::{benchmark_file_name, black_box, drop_file_page_cache, Control, Sampling};
+#[cfg(target_os = "linux")]
+use rand::seq::SliceRandom;
+#[cfg(target_os = "linux")]
+use rand::thread_rng;
+#[cfg(target_os = "linux")]
+use std::fs::{self, OpenOptions};
+#[cfg(target_os = "linux")]
+use std::io::{self, Write};
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+
+// Touched a page at a time, a minor fault takes a few seconds to get through it.
+#[cfg(target_os = "linux")]
+const MINOR_FAULT_SIZE: usize = n_gib_bytes!(1) as usize;
+// Each page is a read from disk, so this is plenty for the measurement window.
+#[cfg(target_os = "linux")]
+const MAJOR_FAULT_FILE_SIZE: usize = n_mib_bytes!(256) as usize;
+// What a THP would be, in 4 KiB pages so it's 512 faults.
+const DONTNEED_SIZE: usize = n_mib_bytes!(2) as usize;
+
+pub fn register(registry: &mut Registry) {
+    #[cfg(target_os = "linux")]
+    for fault in faults() {
+        registry.register(PageFault::new(fault));
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Fault {
+    Minor,
+    Major,
+    // madvise(MADV_DONTNEED) of a touched region, then touching every page of it again.
+    DontNeed,
+    // mmap(2) and munmap(2) of this many bytes without touching them.
+    Mmap(usize),
+}
+
+// 4 KiB to 1 GiB: a page, a small malloc arena, a huge page, a large buffer and a heap.
+fn mmap_sizes() -> [usize; 5] {
+    [
+        n_kib_bytes!(4) as usize,
+        n_kib_bytes!(64) as usize,
+        n_mib_bytes!(2) as usize,
+        n_mib_bytes!(64) as usize,
+        n_gib_bytes!(1) as usize,
+    ]
+}
+
+pub fn faults() -> Vec<Fault> {
+    let mut faults = vec![Fault::Minor, Fault::Major, Fault::DontNeed];
+    faults.extend(mmap_sizes().iter().map(|size| Fault::Mmap(*size)));
+    faults
+}
+
+impl Fault {
+    // How many pages one iteration faults in, so `report faults` can give the cost per page. 0 for
+    // mmap + munmap, which doesn't fault anything in.
+    pub fn pages_per_iteration(&self) -> usize {
+        match self {
+            Fault::Minor | Fault::Major => 1,
+            Fault::DontNeed => DONTNEED_SIZE / page_size(),
+            Fault::Mmap(_) => 0,
+        }
+    }
+}
+
+pub fn fault_title(fault: Fault) -> String {
+    match fault {
+        Fault::Minor => String::from("Minor page fault, anonymous page"),
+        Fault::Major => String::from("Major page fault, file-backed page"),
+        Fault::DontNeed => format!("MADV_DONTNEED + re-fault {}", format_size(DONTNEED_SIZE)),
+        Fault::Mmap(size) => format!("mmap + munmap {}", format_size(size)),
+    }
+}
+
+pub fn page_size() -> usize {
+    #[cfg(unix)]
+    {
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+    #[cfg(not(unix))]
+    {
+        n_kib_bytes!(4) as usize
+    }
+}
+
+// Anonymous and in 4 KiB pages, or THP would turn 512 faults into one.
+#[cfg(target_os = "linux")]
+fn map_anonymous(length: usize) -> *mut u8 {
+    unsafe {
+        let address = libc::mmap(
+            std::ptr::null_mut(),
+            length,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        assert!(
+            address != libc::MAP_FAILED,
+            "mmap(2): {}",
+            std::io::Error::last_os_error()
+        );
+        libc::madvise(address, length, libc::MADV_NOHUGEPAGE);
+        address as *mut u8
+    }
+}
+
+// Writing the file takes longer than faulting it in, so it's kept around between setups and only
+// dropped from the page cache again, like the sequential disk read's.
+#[cfg(target_os = "linux")]
+fn major_fault_file() -> fs::File {
+    let file_name = benchmark_file_name();
+    let exists = fs::metadata(&file_name)
+        .is_ok_and(|metadata| metadata.len() == MAJOR_FAULT_FILE_SIZE as u64);
+    if !exists {
+        let buffer = vec![1; MAJOR_FAULT_FILE_SIZE];
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(&file_name)
+            .unwrap();
+        file.write_all(&buffer).unwrap();
+        // Dirty pages stay in the page cache, whatever we advise.
+        file.sync_data().unwrap();
+    }
+
+    let file = OpenOptions::new().read(true).open(&file_name).unwrap();
+    drop_file_page_cache(&file);
+    file
+}
+
+#[cfg(target_os = "linux")]
+pub struct PageFaultTest {
+    // Null for mmap + munmap, which maps its own every iteration.
+    address: *mut u8,
+    length: usize,
+    // Pages in the order the major faults read them, shuffled so readahead can't guess the next
+    // one. Empty for the others.
+    order: Vec<usize>,
+    i: usize,
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for PageFaultTest {
+    fn drop(&mut self) {
+        if !self.address.is_null() {
+            unsafe { libc::munmap(self.address as *mut libc::c_void, self.length) };
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+struct PageFault {
+    fault: Fault,
+    name: &'static str,
+    title: &'static str,
+}
+
+#[cfg(target_os = "linux")]
+impl PageFault {
+    fn new(fault: Fault) -> PageFault {
+        PageFault {
+            fault,
+            name: match fault {
+                Fault::Minor => "page_fault_minor",
+                Fault::Major => "page_fault_major",
+                // e.g. page_fault_madvise_dontneed_2mib and mmap_munmap_64kib.
+                Fault::DontNeed => sweep_name("page_fault_madvise_dontneed", DONTNEED_SIZE),
+                Fault::Mmap(size) => sweep_name("mmap_munmap", size),
+            },
+            title: Box::leak(fault_title(fault).into_boxed_str()),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Benchmark for PageFault {
+    type State = PageFaultTest;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn description(&self) -> &'static str {
+        match self.fault {
+            Fault::Minor => "Writes to one fresh page of a 1 GiB anonymous mapping at a time.",
+            Fault::Major => {
+                "Reads random pages of a 256 MiB file mapping dropped from the page cache."
+            }
+            Fault::DontNeed => "madvise(MADV_DONTNEED)s 2 MiB and writes to each of its pages.",
+            Fault::Mmap(_) => "mmap(2)s an anonymous region and munmap(2)s it untouched.",
+        }
+    }
+
+    fn category(&self) -> Category {
+        match self.fault {
+            Fault::Minor | Fault::DontNeed => Category::Memory,
+            Fault::Major => Category::Disk,
+            Fault::Mmap(_) => Category::Syscall,
+        }
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        match self.fault {
+            Fault::Major => &["faults", "random", "read"],
+            Fault::Mmap(_) => &["faults", "mmap"],
+            _ => &["faults", "write"],
+        }
+    }
+
+    fn requirements(&self) -> &'static [Requirement] {
+        &[Requirement::Linux]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        self.fault.pages_per_iteration() * page_size()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        match self.fault {
+            Fault::Minor => MINOR_FAULT_SIZE,
+            Fault::Major => MAJOR_FAULT_FILE_SIZE,
+            _ => 0,
+        }
+    }
+
+    fn disk_footprint(&self) -> usize {
+        match self.fault {
+            Fault::Major => MAJOR_FAULT_FILE_SIZE,
+            _ => 0,
+        }
+    }
+
+    // A major fault is a trip to disk, its tail is worth having.
+    fn sampling(&self) -> Sampling {
+        match self.fault {
+            Fault::Major => Sampling::PerIteration,
+            _ => Sampling::Batched,
+        }
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(match self.fault {
+            Fault::Minor => PageFaultTest {
+                address: map_anonymous(MINOR_FAULT_SIZE),
+                length: MINOR_FAULT_SIZE,
+                order: Vec::new(),
+                i: 0,
+            },
+            Fault::Major => {
+                let file = major_fault_file();
+                let address = unsafe {
+                    libc::mmap(
+                        std::ptr::null_mut(),
+                        MAJOR_FAULT_FILE_SIZE,
+                        libc::PROT_READ,
+                        libc::MAP_SHARED,
+                        file.as_raw_fd(),
+                        0,
+                    )
+                };
+                assert!(
+                    address != libc::MAP_FAILED,
+                    "mmap(2): {}",
+                    std::io::Error::last_os_error()
+                );
+                // Without it a fault reads ahead and the next few are minor.
+                unsafe { libc::madvise(address, MAJOR_FAULT_FILE_SIZE, libc::MADV_RANDOM) };
+                let mut order: Vec<usize> = (0..MAJOR_FAULT_FILE_SIZE / page_size()).collect();
+                order.shuffle(&mut thread_rng());
+                PageFaultTest {
+                    address: address as *mut u8,
+                    length: MAJOR_FAULT_FILE_SIZE,
+                    order,
+                    i: 0,
+                }
+            }
+            Fault::DontNeed => {
+                let address = map_anonymous(DONTNEED_SIZE);
+                unsafe { std::ptr::write_bytes(address, 1, DONTNEED_SIZE) };
+                PageFaultTest {
+                    address,
+                    length: DONTNEED_SIZE,
+                    order: Vec::new(),
+                    i: 0,
+                }
+            }
+            Fault::Mmap(_) => PageFaultTest {
+                address: std::ptr::null_mut(),
+                length: 0,
+                order: Vec::new(),
+                i: 0,
+            },
+        })
+    }
+
+    // The minor and major faults run out of fresh pages eventually, a new mapping (and dropping the
+    // file from the page cache again) happens outside of the measurement.
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        let page = page_size();
+        match self.fault {
+            Fault::Minor => {
+                unsafe { std::ptr::write_volatile(test.address.add(test.i * page), 1) };
+                test.i += 1;
+                if test.i == test.length / page {
+                    return Control::ResetWithSetup;
+                }
+            }
+            Fault::Major => {
+                let offset = test.order[test.i] * page;
+                black_box(unsafe { std::ptr::read_volatile(test.address.add(offset)) });
+                test.i += 1;
+                if test.i == test.order.len() {
+                    return Control::ResetWithSetup;
+                }
+            }
+            Fault::DontNeed => unsafe {
+                libc::madvise(
+                    test.address as *mut libc::c_void,
+                    test.length,
+                    libc::MADV_DONTNEED,
+                );
+                for offset in (0..test.length).step_by(page) {
+                    std::ptr::write_volatile(test.address.add(offset), 1);
+                }
+            },
+            Fault::Mmap(size) => unsafe {
+                let address = libc::mmap(
+                    std::ptr::null_mut(),
+                    size,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                    -1,
+                    0,
+                );
+                assert!(
+                    address != libc::MAP_FAILED,
+                    "mmap(2): {}",
+                    std::io::Error::last_os_error()
+                );
+                libc::munmap(black_box(address), size);
+            },
+        }
+        Control::Continue
+    }
+
+    fn teardown(&self) {
+        if let Fault::Major = self.fault {
+            fs::remove_file(benchmark_file_name()).unwrap();
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use super::{Benchmark, Category, Registry};
+use crate::{black_box, Control};
+use sha2::{Digest, Sha256};
+use std::io;
+
+pub fn register(registry: &mut Registry) {
+    registry.register(HashSha256);
+    registry.register(HashCrc32);
+    registry.register(HashSiphash);
+}
+
+const SIZE_OF_WRITES: usize = 64;
+
+fn random_bytes() -> Vec<u8> {
+    (0..SIZE_OF_WRITES).map(|_| rand::random::<u8>()).collect()
+}
+
+pub struct HashSha256;
+
+impl Benchmark for HashSha256 {
+    type State = Vec<u8>;
+
+    fn name(&self) -> &'static str {
+        "hash_sha256"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sha256"
+    }
+
+    fn description(&self) -> &'static str {
+        "SHA-256 of 64 random bytes."
+    }
+
+    fn category(&self) -> Category {
+        Category::Cpu
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["hash", "crypto"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        SIZE_OF_WRITES
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(random_bytes())
+    }
+
+    fn iteration(&self, bytes: &mut Self::State) -> Control {
+        black_box(Sha256::digest(bytes));
+        Control::Continue
+    }
+}
+
+pub struct HashCrc32;
+
+impl Benchmark for HashCrc32 {
+    type State = Vec<u8>;
+
+    fn name(&self) -> &'static str {
+        "hash_crc32"
+    }
+
+    fn title(&self) -> &'static str {
+        "CRC32"
+    }
+
+    fn description(&self) -> &'static str {
+        "CRC32 of 64 random bytes."
+    }
+
+    fn category(&self) -> Category {
+        Category::Cpu
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["hash"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        SIZE_OF_WRITES
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(random_bytes())
+    }
+
+    fn iteration(&self, bytes: &mut Self::State) -> Control {
+        use crc32fast::Hasher;
+
+        let mut hasher = Hasher::new();
+        hasher.update(bytes);
+        black_box(hasher.finalize());
+        Control::Continue
+    }
+}
+
+pub struct HashSiphash;
+
+impl Benchmark for HashSiphash {
+    type State = Vec<u8>;
+
+    fn name(&self) -> &'static str {
+        "hash_siphash"
+    }
+
+    fn title(&self) -> &'static str {
+        "SIPHash"
+    }
+
+    fn description(&self) -> &'static str {
+        "SipHash, the std `HashMap` hasher, of 64 random bytes."
+    }
+
+    fn category(&self) -> Category {
+        Category::Cpu
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["hash"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        SIZE_OF_WRITES
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(random_bytes())
+    }
+
+    fn iteration(&self, bytes: &mut Self::State) -> Control {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::Hasher;
+
+        let mut hasher = DefaultHasher::new();
+        hasher.write(bytes);
+        black_box(hasher.finish());
+        Control::Continue
+    }
+}
@@ -0,0 +1,308 @@
+// The cell-layout experiments from `go/main.go` with the Rust harness: summing one f64 per element
+// out of cells padded like `SmallestCell`, `SmallCell`, `SmallerCell` and `LargeCell`, two fields
+// out of an array of structs against the same two columns of a struct of arrays, and a matrix as
+// one flat `Vec` against a `Vec` per row. `report layout` puts the time per element side by side.
+
+use super::{Benchmark, Category, Registry};
+use crate::{black_box, Control, Sampling};
+use std::io;
+
+// 8M elements, 64 MiB of values and 704 MiB of `LargeCell`s: past the caches for every layout.
+// `report layout` divides by it to get the time per element.
+pub const LAYOUT_ELEMENTS: usize = 1 << 23;
+// Columns per row of the matrix, short enough that the nested rows' allocations show.
+const ROW_LENGTH: usize = 64;
+
+pub fn register(registry: &mut Registry) {
+    for layout in LAYOUTS.iter() {
+        registry.register(LayoutScan::new(*layout));
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Layout {
+    // Cells with this many bytes of padding in front of their value.
+    Padded0,
+    Padded8,
+    Padded24,
+    Padded56,
+    Padded80,
+    ArrayOfStructs,
+    StructOfArrays,
+    Flat,
+    Nested,
+}
+
+// The paddings are the ones of `Cell`, `SmallestCell`, `SmallCell`, `SmallerCell` and `LargeCell`.
+pub const LAYOUTS: [Layout; 9] = [
+    Layout::Padded0,
+    Layout::Padded8,
+    Layout::Padded24,
+    Layout::Padded56,
+    Layout::Padded80,
+    Layout::ArrayOfStructs,
+    Layout::StructOfArrays,
+    Layout::Flat,
+    Layout::Nested,
+];
+
+impl Layout {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Layout::Padded0 => "padded_0",
+            Layout::Padded8 => "padded_8",
+            Layout::Padded24 => "padded_24",
+            Layout::Padded56 => "padded_56",
+            Layout::Padded80 => "padded_80",
+            Layout::ArrayOfStructs => "aos",
+            Layout::StructOfArrays => "soa",
+            Layout::Flat => "flat",
+            Layout::Nested => "nested",
+        }
+    }
+
+    // What's in memory for each element, useful or not.
+    pub fn element_size(&self) -> usize {
+        match self {
+            Layout::Padded0 => std::mem::size_of::<Cell<0>>(),
+            Layout::Padded8 => std::mem::size_of::<Cell<8>>(),
+            Layout::Padded24 => std::mem::size_of::<Cell<24>>(),
+            Layout::Padded56 => std::mem::size_of::<Cell<56>>(),
+            Layout::Padded80 => std::mem::size_of::<Cell<80>>(),
+            Layout::ArrayOfStructs => std::mem::size_of::<Trade>(),
+            Layout::StructOfArrays => 16,
+            Layout::Flat | Layout::Nested => 8,
+        }
+    }
+
+    // What the scan actually uses of each element.
+    fn useful_bytes(&self) -> usize {
+        match self {
+            Layout::ArrayOfStructs | Layout::StructOfArrays => 16,
+            _ => 8,
+        }
+    }
+}
+
+pub fn layout_title(layout: Layout) -> String {
+    let cells =
+        |padding: usize| format!("Scan Vec of {} B cells, {} B padding", padding + 8, padding);
+    match layout {
+        Layout::Padded0 => cells(0),
+        Layout::Padded8 => cells(8),
+        Layout::Padded24 => cells(24),
+        Layout::Padded56 => cells(56),
+        Layout::Padded80 => cells(80),
+        Layout::ArrayOfStructs => String::from("Scan array of structs, 2 of 8 fields"),
+        Layout::StructOfArrays => String::from("Scan struct of arrays, 2 columns"),
+        Layout::Flat => format!("Scan flat Vec<f64> matrix, {} columns", ROW_LENGTH),
+        Layout::Nested => format!("Scan nested Vec<Vec<f64>> matrix, {} columns", ROW_LENGTH),
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct Cell<const PADDING: usize> {
+    padding: [u8; PADDING],
+    value: f64,
+}
+
+// A row the way an ORM would load it, when all we want is the notional.
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub struct Trade {
+    id: u64,
+    price: f64,
+    quantity: f64,
+    timestamp: u64,
+    account: u64,
+    venue: u64,
+    side: u64,
+    flags: u64,
+}
+
+pub enum LayoutState {
+    Padded0(Vec<Cell<0>>),
+    Padded8(Vec<Cell<8>>),
+    Padded24(Vec<Cell<24>>),
+    Padded56(Vec<Cell<56>>),
+    Padded80(Vec<Cell<80>>),
+    Trades(Vec<Trade>),
+    Columns { price: Vec<f64>, quantity: Vec<f64> },
+    Flat(Vec<f64>),
+    Nested(Vec<Vec<f64>>),
+}
+
+fn cells<const PADDING: usize>() -> Vec<Cell<PADDING>> {
+    (0..LAYOUT_ELEMENTS)
+        .map(|i| Cell {
+            padding: [0; PADDING],
+            value: i as f64,
+        })
+        .collect()
+}
+
+// Independent running sums. With one, every add waits on the one before it and the scan is bound
+// by add latency instead of by memory.
+const ACCUMULATORS: usize = 8;
+
+fn sum(mut values: impl Iterator<Item = f64>) -> f64 {
+    let mut sums = [0.0; ACCUMULATORS];
+    loop {
+        for sum in sums.iter_mut() {
+            match values.next() {
+                Some(value) => *sum += value,
+                None => return sums.iter().sum(),
+            }
+        }
+    }
+}
+
+// Rows are a multiple of `ACCUMULATORS` long, so each one adds evenly across the sums.
+fn add_row(sums: &mut [f64; ACCUMULATORS], row: &[f64]) {
+    for values in row.chunks_exact(ACCUMULATORS) {
+        for (sum, value) in sums.iter_mut().zip(values.iter()) {
+            *sum += value;
+        }
+    }
+}
+
+fn sum_cells<const PADDING: usize>(cells: &[Cell<PADDING>]) -> f64 {
+    sum(cells.iter().map(|cell| cell.value))
+}
+
+pub struct LayoutScan {
+    layout: Layout,
+    name: &'static str,
+    title: &'static str,
+}
+
+impl LayoutScan {
+    fn new(layout: Layout) -> LayoutScan {
+        // e.g. layout_scan_padded_24.
+        let name = format!("layout_scan_{}", layout.as_str());
+        LayoutScan {
+            layout,
+            name: Box::leak(name.into_boxed_str()),
+            title: Box::leak(layout_title(layout).into_boxed_str()),
+        }
+    }
+}
+
+impl Benchmark for LayoutScan {
+    type State = LayoutState;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn description(&self) -> &'static str {
+        match self.layout {
+            Layout::Padded0
+            | Layout::Padded8
+            | Layout::Padded24
+            | Layout::Padded56
+            | Layout::Padded80 => "Sums the f64 of 8M padded cells, like the Go cell experiments.",
+            Layout::ArrayOfStructs => "Sums price * quantity over 8M 64 byte structs.",
+            Layout::StructOfArrays => "Sums price * quantity over two 8M element columns.",
+            Layout::Flat => "Sums an 8M element matrix stored as one Vec, row by row.",
+            Layout::Nested => "Sums an 8M element matrix stored as a Vec per row.",
+        }
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["layout", "sequential", "read"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        LAYOUT_ELEMENTS * self.layout.useful_bytes()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        LAYOUT_ELEMENTS * self.layout.element_size()
+    }
+
+    fn sampling(&self) -> Sampling {
+        Sampling::PerIteration
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(match self.layout {
+            Layout::Padded0 => LayoutState::Padded0(cells()),
+            Layout::Padded8 => LayoutState::Padded8(cells()),
+            Layout::Padded24 => LayoutState::Padded24(cells()),
+            Layout::Padded56 => LayoutState::Padded56(cells()),
+            Layout::Padded80 => LayoutState::Padded80(cells()),
+            Layout::ArrayOfStructs => LayoutState::Trades(
+                (0..LAYOUT_ELEMENTS as u64)
+                    .map(|i| Trade {
+                        id: i,
+                        price: i as f64,
+                        quantity: 2.0,
+                        timestamp: i,
+                        account: i,
+                        venue: i,
+                        side: i,
+                        flags: i,
+                    })
+                    .collect(),
+            ),
+            Layout::StructOfArrays => LayoutState::Columns {
+                price: (0..LAYOUT_ELEMENTS).map(|i| i as f64).collect(),
+                quantity: vec![2.0; LAYOUT_ELEMENTS],
+            },
+            Layout::Flat => LayoutState::Flat((0..LAYOUT_ELEMENTS).map(|i| i as f64).collect()),
+            Layout::Nested => LayoutState::Nested(
+                (0..LAYOUT_ELEMENTS / ROW_LENGTH)
+                    .map(|row| {
+                        (0..ROW_LENGTH)
+                            .map(|column| (row * ROW_LENGTH + column) as f64)
+                            .collect()
+                    })
+                    .collect(),
+            ),
+        })
+    }
+
+    fn iteration(&self, state: &mut Self::State) -> Control {
+        let sum: f64 = match state {
+            LayoutState::Padded0(cells) => sum_cells(cells),
+            LayoutState::Padded8(cells) => sum_cells(cells),
+            LayoutState::Padded24(cells) => sum_cells(cells),
+            LayoutState::Padded56(cells) => sum_cells(cells),
+            LayoutState::Padded80(cells) => sum_cells(cells),
+            LayoutState::Trades(trades) => {
+                sum(trades.iter().map(|trade| trade.price * trade.quantity))
+            }
+            LayoutState::Columns { price, quantity } => sum(price
+                .iter()
+                .zip(quantity.iter())
+                .map(|(price, quantity)| price * quantity)),
+            // Row by row like the nested one, so all that differs is where the rows live.
+            LayoutState::Flat(matrix) => {
+                let mut sums = [0.0; ACCUMULATORS];
+                for row in matrix.chunks_exact(ROW_LENGTH) {
+                    add_row(&mut sums, row);
+                }
+                sums.iter().sum()
+            }
+            LayoutState::Nested(matrix) => {
+                let mut sums = [0.0; ACCUMULATORS];
+                for row in matrix.iter() {
+                    add_row(&mut sums, row);
+                }
+                sums.iter().sum()
+            }
+        };
+        black_box(sum);
+        Control::Continue
+    }
+}
@@ -0,0 +1,715 @@
+use super::{Benchmark, Category, Registry};
+use crate::report::format_size;
+use crate::{black_box, Control};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use std::io;
+use std::sync::Arc;
+use std::thread;
+
+pub fn register(registry: &mut Registry) {
+    registry.register(MemoryReadSequentialThreaded);
+    registry.register(MemoryReadSequential);
+    registry.register(MemoryWriteSequential);
+    registry.register(MemoryReadRandom);
+    registry.register(MemoryReadDependent);
+    registry.register(MemoryWriteRandom);
+    for size in random_read_sweep() {
+        registry.register(MemoryReadRandomSweep::new(size));
+    }
+    for size in random_read_sweep() {
+        registry.register(MemoryReadDependentSweep::new(size));
+    }
+    for stride in stride_sweep() {
+        registry.register(MemoryReadStride::new(stride));
+    }
+}
+
+pub struct MemoryWriteSequential;
+
+pub struct MemoryWriteSequentialTest {
+    i: usize,
+    vec: Vec<[u64; 8]>,
+}
+
+impl Benchmark for MemoryWriteSequential {
+    type State = MemoryWriteSequentialTest;
+
+    fn name(&self) -> &'static str {
+        "memory_write_sequential"
+    }
+
+    fn title(&self) -> &'static str {
+        "Write Seq Vec"
+    }
+
+    fn description(&self) -> &'static str {
+        "Writes a 1 GB vector front to back, 64 bytes at a time."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["sequential", "write"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        64
+    }
+
+    fn memory_footprint(&self) -> usize {
+        n_gb_bytes!(1) as usize
+    }
+
+    fn stops_early(&self) -> bool {
+        true
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let size_in_elements = n_gb_bytes!(1) as usize / self.bytes_per_iteration();
+        let mut vec = Vec::new();
+        vec.resize(size_in_elements, [1, 2, 3, 4, 5, 6, 7, 8]);
+        Ok(MemoryWriteSequentialTest { i: 0, vec })
+    }
+
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        test.vec[test.i] = [8, 7, 110_694, 5, 4, 3, 2, 1];
+        black_box(test.vec[test.i]);
+        test.i += 1;
+        if test.i == test.vec.len() {
+            return Control::Stop;
+        }
+        Control::Continue
+    }
+}
+
+pub struct MemoryReadSequential;
+
+pub struct MemoryReadSequentialTest {
+    i: usize,
+    vec: Vec<[u64; 8]>,
+    total: u64,
+}
+
+impl Benchmark for MemoryReadSequential {
+    type State = MemoryReadSequentialTest;
+
+    fn name(&self) -> &'static str {
+        "memory_read_sequential"
+    }
+
+    fn title(&self) -> &'static str {
+        "Read Seq Vec"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reads a 4 GB vector front to back, 64 bytes at a time."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["sequential", "read"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        64
+    }
+
+    fn memory_footprint(&self) -> usize {
+        n_gb_bytes!(4) as usize
+    }
+
+    fn stops_early(&self) -> bool {
+        true
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let size_in_elements = n_gb_bytes!(4) as u64 / self.bytes_per_iteration() as u64;
+        let mut vec: Vec<[u64; 8]> = Vec::new();
+        for i in 0..size_in_elements {
+            vec.push([i, i, i, i, i, i, i, i]);
+        }
+
+        // println!("Length: {}", vec.len());
+        Ok(MemoryReadSequentialTest {
+            i: 0,
+            vec,
+            total: 0,
+        })
+    }
+
+    // put these in separate functions so they can be disassembled.
+    // #[inline] is going to be important here.
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        if test.i == test.vec.len() {
+            // println!("Result: {}", test.total);
+            Control::Stop
+        } else {
+            black_box(test.vec[test.i]);
+            test.total += test.vec[test.i][0];
+            test.i += 1;
+            Control::Continue
+        }
+    }
+}
+
+// USE https://github.com/bheisler/criterion.rs instead... Just too much of a hassle to setup
+// threads etc. and we should've done this from the get-go anyway.
+pub struct MemoryReadSequentialThreaded;
+
+impl Benchmark for MemoryReadSequentialThreaded {
+    type State = Arc<Vec<[u64; 8]>>;
+
+    fn name(&self) -> &'static str {
+        "memory_read_sequential_threaded"
+    }
+
+    fn title(&self) -> &'static str {
+        "Read Seq Vec Threaded"
+    }
+
+    fn description(&self) -> &'static str {
+        "Four threads each sum a quarter of a 4 GB vector, an iteration reads all of it."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["sequential", "read", "threaded"]
+    }
+
+    // The whole vector is read by the threads on every iteration.
+    fn bytes_per_iteration(&self) -> usize {
+        n_gb_bytes!(4) as usize
+    }
+
+    fn memory_footprint(&self) -> usize {
+        n_gb_bytes!(4) as usize
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let size_in_elements = (n_gb_bytes!(4) as u64 / 64) as u64;
+        let mut vec: Vec<[u64; 8]> = Vec::new();
+        for i in 0..size_in_elements {
+            vec.push([i, i, i, i, i, i, i, i]);
+        }
+
+        Ok(Arc::new(vec))
+    }
+
+    fn iteration(&self, vec: &mut Self::State) -> Control {
+        let mut threads: Vec<thread::JoinHandle<_>> = vec![];
+        let n_threads = 4;
+        for k in 0..(n_threads) {
+            let my_vec = vec.clone();
+            let mut start = (my_vec.len() / n_threads) * k;
+            let mut end = start + (my_vec.len() / n_threads);
+            if k != 0 {
+                start += 1;
+                end += 1;
+            }
+
+            if k == n_threads - 1 {
+                end -= 1;
+            }
+
+            // println!("{}..{}", start, end);
+
+            threads.push(thread::spawn(move || {
+                let mut i: u64 = 0;
+
+                for j in start..end {
+                    i += my_vec[j][0];
+                    black_box(my_vec[j]);
+                }
+
+                i
+            }));
+        }
+
+        // let mut result = 0;
+        for thread in threads {
+            // result += thread.join().unwrap();
+            thread.join().unwrap();
+        }
+        // print!("Result {}\n", result);
+
+        Control::Continue
+    }
+}
+
+pub struct MemoryWriteRandom;
+
+pub struct MemoryWriteRandomTest {
+    vec: Vec<[u64; 8]>,
+    order: Vec<usize>,
+    i: usize,
+}
+
+impl Benchmark for MemoryWriteRandom {
+    type State = MemoryWriteRandomTest;
+
+    fn name(&self) -> &'static str {
+        "memory_write_random"
+    }
+
+    fn title(&self) -> &'static str {
+        "Random Write Vec"
+    }
+
+    fn description(&self) -> &'static str {
+        "Writes 64 bytes at a time to a 1 GB vector in shuffled order."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["random", "write"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        64
+    }
+
+    fn memory_footprint(&self) -> usize {
+        n_gb_bytes!(1) as usize + n_gb_bytes!(1) as usize / 64 * std::mem::size_of::<usize>()
+    }
+
+    fn stops_early(&self) -> bool {
+        true
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let size_in_elements = n_gb_bytes!(1) as usize / self.bytes_per_iteration();
+        let mut vec = Vec::new();
+        vec.resize(size_in_elements, [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let mut order: Vec<usize> = (0..size_in_elements).collect();
+        order.shuffle(&mut thread_rng());
+        Ok(MemoryWriteRandomTest { vec, order, i: 0 })
+    }
+
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        test.vec[test.order[test.i]] = [8, 7, 6, 5, 4, 3, 2, 1];
+        black_box(test.vec[test.order[test.i]]);
+        test.i += 1;
+        if test.i == test.vec.len() {
+            return Control::Stop;
+        }
+        Control::Continue
+    }
+}
+
+pub struct MemoryReadRandom;
+
+pub struct MemoryReadTest {
+    vec: Vec<[u64; 8]>,
+    order: Vec<usize>,
+    i: usize,
+}
+
+impl Benchmark for MemoryReadRandom {
+    type State = MemoryReadTest;
+
+    fn name(&self) -> &'static str {
+        "memory_read_random"
+    }
+
+    fn title(&self) -> &'static str {
+        "Random Read Vec"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reads 64 bytes at a time from a 1 GB vector in shuffled order."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["random", "read"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        64
+    }
+
+    fn memory_footprint(&self) -> usize {
+        n_gb_bytes!(1) as usize + n_gb_bytes!(1) as usize / 64 * std::mem::size_of::<usize>()
+    }
+
+    fn stops_early(&self) -> bool {
+        true
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(memory_read_random_setup(n_gb_bytes!(1) as usize))
+    }
+
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        memory_read_random_iteration(test)
+    }
+}
+
+fn memory_read_random_setup(size: usize) -> MemoryReadTest {
+    let size_in_elements = size / 64;
+
+    let mut vec = Vec::new();
+    vec.resize(size_in_elements, [1, 2, 3, 4, 5, 6, 7, 8]);
+    unsafe {
+        let data = vec.as_mut_ptr() as *mut libc::c_void;
+        libc::madvise(
+            data,
+            size_in_elements * std::mem::size_of::<[u64; 8]>(),
+            libc::MADV_RANDOM,
+        );
+    }
+
+    let mut order: Vec<usize> = (0..size_in_elements).collect();
+    unsafe {
+        let data = order.as_mut_ptr() as *mut libc::c_void;
+        libc::madvise(
+            data,
+            size_in_elements * std::mem::size_of::<usize>(),
+            libc::MADV_SEQUENTIAL,
+        );
+    }
+    order.shuffle(&mut thread_rng());
+    MemoryReadTest { vec, order, i: 0 }
+}
+
+#[inline(always)]
+fn memory_read_random_iteration(test: &mut MemoryReadTest) -> Control {
+    black_box(test.vec[test.order[test.i]]);
+    test.i += 1;
+    if test.i == test.vec.len() {
+        return Control::Stop;
+    }
+    Control::Continue
+}
+
+// 4 KiB to 4 GiB in powers of two, enough to walk off the end of every cache level into DRAM.
+pub fn random_read_sweep() -> impl Iterator<Item = usize> {
+    (12..=32).map(|shift| 1 << shift)
+}
+
+pub fn random_read_sweep_title(size: usize) -> String {
+    format!("Random Read Vec, {} working set", format_size(size))
+}
+
+pub fn dependent_read_sweep_title(size: usize) -> String {
+    format!("Dependent Read Vec, {} working set", format_size(size))
+}
+
+// e.g. memory_read_random_32kib. Registered once and kept for the whole run, so leaking is what
+// `&'static` asks for.
+pub(super) fn sweep_name(prefix: &str, size: usize) -> &'static str {
+    let name = format!(
+        "{}_{}",
+        prefix,
+        format_size(size).replace(' ', "").to_lowercase()
+    );
+    Box::leak(name.into_boxed_str())
+}
+
+// `memory_read_random` with the working set as a parameter. Unlike `memory_read_random` it starts
+// over when it's been through the vector, the small sizes would otherwise be done in microseconds.
+pub struct MemoryReadRandomSweep {
+    size: usize,
+    name: &'static str,
+    title: &'static str,
+}
+
+impl MemoryReadRandomSweep {
+    fn new(size: usize) -> MemoryReadRandomSweep {
+        MemoryReadRandomSweep {
+            size,
+            name: sweep_name("memory_read_random", size),
+            title: Box::leak(random_read_sweep_title(size).into_boxed_str()),
+        }
+    }
+}
+
+impl Benchmark for MemoryReadRandomSweep {
+    type State = MemoryReadTest;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn description(&self) -> &'static str {
+        "One step of the working-set sweep behind `report caches`, 64 byte reads in shuffled order."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["random", "read", "sweep"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        64
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.size + self.size / 64 * std::mem::size_of::<usize>()
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(memory_read_random_setup(self.size))
+    }
+
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        black_box(test.vec[test.order[test.i]]);
+        test.i += 1;
+        if test.i == test.vec.len() {
+            test.i = 0;
+        }
+        Control::Continue
+    }
+}
+
+// `memory_read_random` issues its loads independently of each other, so the CPU has many misses in
+// flight at once and what we measure is closer to throughput than latency. Here every element
+// holds the index of the next one to read, so each load waits for the one before it: that's
+// load-to-use latency.
+pub struct MemoryReadDependent;
+
+pub struct MemoryReadDependentTest {
+    vec: Vec<[u64; 8]>,
+    i: usize,
+}
+
+impl Benchmark for MemoryReadDependent {
+    type State = MemoryReadDependentTest;
+
+    fn name(&self) -> &'static str {
+        "memory_read_dependent"
+    }
+
+    fn title(&self) -> &'static str {
+        "Dependent Read Vec"
+    }
+
+    fn description(&self) -> &'static str {
+        "Follows a single random cycle through a 1 GB vector, each 64 byte read needs the previous one."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["random", "read", "latency"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        64
+    }
+
+    fn memory_footprint(&self) -> usize {
+        n_gb_bytes!(1) as usize + n_gb_bytes!(1) as usize / 64 * std::mem::size_of::<usize>()
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(memory_read_dependent_setup(n_gb_bytes!(1) as usize))
+    }
+
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        memory_read_dependent_iteration(test)
+    }
+}
+
+fn memory_read_dependent_setup(size: usize) -> MemoryReadDependentTest {
+    let size_in_elements = size / 64;
+
+    let mut vec = Vec::new();
+    vec.resize(size_in_elements, [0u64; 8]);
+    unsafe {
+        let data = vec.as_mut_ptr() as *mut libc::c_void;
+        libc::madvise(
+            data,
+            size_in_elements * std::mem::size_of::<[u64; 8]>(),
+            libc::MADV_RANDOM,
+        );
+    }
+
+    // Linking each element to the next one in a shuffled order makes a single cycle through all
+    // of them, so we never get stuck in a small loop that fits in cache.
+    let mut order: Vec<usize> = (0..size_in_elements).collect();
+    order.shuffle(&mut thread_rng());
+    for k in 0..size_in_elements {
+        vec[order[k]][0] = order[(k + 1) % size_in_elements] as u64;
+    }
+
+    MemoryReadDependentTest { vec, i: order[0] }
+}
+
+#[inline(always)]
+fn memory_read_dependent_iteration(test: &mut MemoryReadDependentTest) -> Control {
+    test.i = black_box(test.vec[test.i][0]) as usize;
+    Control::Continue
+}
+
+// `memory_read_dependent` over the same working sets as `MemoryReadRandomSweep`, so `report caches`
+// can put latency and throughput next to each other.
+pub struct MemoryReadDependentSweep {
+    size: usize,
+    name: &'static str,
+    title: &'static str,
+}
+
+impl MemoryReadDependentSweep {
+    fn new(size: usize) -> MemoryReadDependentSweep {
+        MemoryReadDependentSweep {
+            size,
+            name: sweep_name("memory_read_dependent", size),
+            title: Box::leak(dependent_read_sweep_title(size).into_boxed_str()),
+        }
+    }
+}
+
+impl Benchmark for MemoryReadDependentSweep {
+    type State = MemoryReadDependentTest;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn description(&self) -> &'static str {
+        "One step of the working-set sweep behind `report caches`, a random cycle of dependent reads."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["random", "read", "latency", "sweep"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        64
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.size + self.size / 64 * std::mem::size_of::<usize>()
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(memory_read_dependent_setup(self.size))
+    }
+
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        memory_read_dependent_iteration(test)
+    }
+}
+
+// 8 B (every u64) to 8 KiB in powers of two: past a cache line every read needs a line of its own,
+// past 4 KiB every read is on a page of its own and the prefetchers don't cross pages.
+pub fn stride_sweep() -> impl Iterator<Item = usize> {
+    (3..=13).map(|shift| 1 << shift)
+}
+
+pub fn stride_title(stride: usize) -> String {
+    format!("Strided Read Vec, {} stride", format_size(stride))
+}
+
+// Reads one u64 every `stride` bytes of a 1 GiB vector, far past the last level of cache. Strides
+// over a cache line would only touch `1 GiB / stride` lines per pass and fit in cache at the big
+// ones, so every pass starts a line further in until all lines have been read.
+pub struct MemoryReadStride {
+    stride: usize,
+    name: &'static str,
+    title: &'static str,
+}
+
+pub struct MemoryReadStrideTest {
+    vec: Vec<u64>,
+    start: usize,
+    i: usize,
+}
+
+impl MemoryReadStride {
+    fn new(stride: usize) -> MemoryReadStride {
+        MemoryReadStride {
+            stride,
+            name: sweep_name("memory_read_stride", stride),
+            title: Box::leak(stride_title(stride).into_boxed_str()),
+        }
+    }
+}
+
+impl Benchmark for MemoryReadStride {
+    type State = MemoryReadStrideTest;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn description(&self) -> &'static str {
+        "One step of the stride sweep behind `report strides`, 8 byte reads through a 1 GB vector."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["read", "stride", "sweep"]
+    }
+
+    // What the workload asked for, not the cache lines it pulled in to get it.
+    fn bytes_per_iteration(&self) -> usize {
+        std::mem::size_of::<u64>()
+    }
+
+    fn memory_footprint(&self) -> usize {
+        n_gb_bytes!(1) as usize
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let size_in_elements = n_gb_bytes!(1) as usize / std::mem::size_of::<u64>();
+        Ok(MemoryReadStrideTest {
+            vec: (0..size_in_elements as u64).collect(),
+            start: 0,
+            i: 0,
+        })
+    }
+
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        let stride = self.stride / std::mem::size_of::<u64>();
+        black_box(test.vec[test.i]);
+        test.i += stride;
+        if test.i >= test.vec.len() {
+            // Up to a cache line this stays at 0, every pass already reads every line.
+            test.start = (test.start + 8) % stride;
+            test.i = test.start;
+        }
+        Control::Continue
+    }
+}
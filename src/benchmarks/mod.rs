@@ -0,0 +1,284 @@
+// Every benchmark the binary can run. A benchmark is a type implementing `Benchmark`, and a suite
+// is a module with a `register` function adding its benchmarks to the `Registry`. Adding a suite
+// means adding it to `registry()` below, `main` only ever sees the registry.
+
+use crate::{benchmark, benchmark_threaded, BenchmarkResult, Control, Sampling};
+use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+mod allocator;
+mod bandwidth;
+mod concurrency;
+mod copy;
+mod creation;
+mod database;
+mod disk;
+mod faults;
+mod hash;
+mod layout;
+mod memory;
+mod network;
+mod numa;
+mod pages;
+mod sort;
+mod switch;
+mod syscall;
+
+pub use allocator::{
+    alloc_threaded_title, alloc_title, global_allocator, AllocOp, Allocator, ALLOCATORS, ALLOC_OPS,
+    THREADED_ALLOCATIONS,
+};
+pub use bandwidth::{bandwidth_title, Workload, WORKLOADS};
+pub use concurrency::{
+    atomic_title, core_distance, ping_pong_title, AtomicOp, Distance, Sharing, ATOMIC_OPS,
+    PING_PONG_ROUND_TRIPS, SHARINGS,
+};
+pub use copy::{copy_sweep, copy_threaded_title, copy_title, CopyKernel, COPY_KERNELS};
+pub use creation::{Creation, CREATIONS};
+pub use faults::{fault_title, faults, page_size, Fault};
+pub use layout::{layout_title, Layout, LAYOUTS, LAYOUT_ELEMENTS};
+pub use memory::{
+    dependent_read_sweep_title, random_read_sweep, random_read_sweep_title, stride_sweep,
+    stride_title,
+};
+pub use numa::{numa_title, Placement, NUMA_WORKLOADS};
+pub use pages::{page_read_title, PageRead, Pages, PAGES};
+pub use switch::{switch_title, Mechanism, Peer, SwitchCores, MECHANISMS};
+pub use syscall::{syscall_title, Syscall, SyscallPath, SYSCALLS};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Category {
+    Memory,
+    Syscall,
+    Disk,
+    Network,
+    Database,
+    Cpu,
+    Concurrency,
+}
+
+impl Category {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Category::Memory => "memory",
+            Category::Syscall => "syscall",
+            Category::Disk => "disk",
+            Category::Network => "network",
+            Category::Database => "database",
+            Category::Cpu => "cpu",
+            Category::Concurrency => "concurrency",
+        }
+    }
+}
+
+// Things outside of the binary a benchmark needs. Unmet requirements skip the benchmark rather
+// than panicking halfway through a run.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Requirement {
+    Linux,
+    IoUring,
+    Redis,
+    MySql,
+    Numa,
+    TransparentHugePages,
+    HugeTlbPages,
+    Avx2,
+    MultipleCores,
+}
+
+impl Requirement {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Requirement::Linux => "linux",
+            Requirement::IoUring => "io_uring",
+            Requirement::Redis => "redis on 127.0.0.1:6379",
+            Requirement::MySql => "mysql on 127.0.0.1:3306",
+            Requirement::Numa => "2+ NUMA nodes",
+            Requirement::TransparentHugePages => "transparent huge pages not set to never",
+            Requirement::HugeTlbPages => "1 GiB of free huge pages in vm.nr_hugepages",
+            Requirement::Avx2 => "avx2",
+            Requirement::MultipleCores => "2+ cores",
+        }
+    }
+
+    pub fn is_met(&self) -> bool {
+        match self {
+            Requirement::Linux | Requirement::IoUring => cfg!(target_os = "linux"),
+            Requirement::Redis => listening(6379),
+            Requirement::MySql => listening(3306),
+            Requirement::Numa => crate::numa::local_and_remote().is_some(),
+            Requirement::TransparentHugePages => pages::transparent_huge_pages(),
+            Requirement::HugeTlbPages => pages::huge_tlb_pages(),
+            Requirement::Avx2 => copy::avx2(),
+            Requirement::MultipleCores => {
+                std::thread::available_parallelism().is_ok_and(|n| n.get() > 1)
+            }
+        }
+    }
+}
+
+fn listening(port: u16) -> bool {
+    let address = SocketAddr::from(([127, 0, 0, 1], port));
+    TcpStream::connect_timeout(&address, Duration::from_millis(100)).is_ok()
+}
+
+// A benchmark is a `setup` producing some state, and an `iteration` that's run against it until
+// the measurement window is over. `setup` is run twice, once for warmup and once for the real run.
+pub trait Benchmark {
+    type State;
+
+    // What `-e` matches against, e.g. `memory_read_random`.
+    fn name(&self) -> &'static str;
+    // What the results are reported as, e.g. `Random Read Vec`. Baselines are keyed by it and
+    // `report` looks results up by it, so changing one orphans its saved results and report rows.
+    fn title(&self) -> &'static str;
+    // One sentence on what's being measured, for `list`.
+    fn description(&self) -> &'static str;
+    fn category(&self) -> Category;
+    fn tags(&self) -> &'static [&'static str] {
+        &[]
+    }
+    fn requirements(&self) -> &'static [Requirement] {
+        &[]
+    }
+    // 0 for benchmarks where throughput doesn't make sense, e.g. syscalls.
+    fn bytes_per_iteration(&self) -> usize {
+        0
+    }
+    // Roughly what `setup` allocates and writes to `benchmark_file_name()`, so `list` can warn
+    // before someone fills up their laptop. Both are 0 when it's negligible.
+    fn memory_footprint(&self) -> usize {
+        0
+    }
+    fn disk_footprint(&self) -> usize {
+        0
+    }
+    fn sampling(&self) -> Sampling {
+        Sampling::Batched
+    }
+    // True when iterations hand the work to threads (or processes) `setup` started, which the perf
+    // counters can't see. See `benchmark_threaded`.
+    fn threaded(&self) -> bool {
+        false
+    }
+    // True when `iteration` can return `Control::Stop` before the measurement window is over, e.g.
+    // at the end of a working set it only goes through once. `list` gives these an upper bound.
+    fn stops_early(&self) -> bool {
+        false
+    }
+
+    // An error skips the benchmark, e.g. when the kernel won't hand out what it asked for.
+    fn setup(&self) -> io::Result<Self::State>;
+    // Tells `benchmark` whether to keep going, re-run `setup` or stop, see `Control`.
+    fn iteration(&self, state: &mut Self::State) -> Control;
+    // Runs once after measuring, e.g. to remove files.
+    fn teardown(&self) {}
+}
+
+// `Benchmark` has an associated type, so the registry stores them through this object-safe view.
+pub trait DynBenchmark {
+    fn name(&self) -> &'static str;
+    fn title(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    fn category(&self) -> Category;
+    fn tags(&self) -> &'static [&'static str];
+    fn requirements(&self) -> &'static [Requirement];
+    fn bytes_per_iteration(&self) -> usize;
+    fn memory_footprint(&self) -> usize;
+    fn disk_footprint(&self) -> usize;
+    fn stops_early(&self) -> bool;
+    fn run(&self) -> io::Result<BenchmarkResult>;
+}
+
+impl<B: Benchmark> DynBenchmark for B {
+    fn name(&self) -> &'static str {
+        Benchmark::name(self)
+    }
+
+    fn title(&self) -> &'static str {
+        Benchmark::title(self)
+    }
+
+    fn description(&self) -> &'static str {
+        Benchmark::description(self)
+    }
+
+    fn category(&self) -> Category {
+        Benchmark::category(self)
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        Benchmark::tags(self)
+    }
+
+    fn requirements(&self) -> &'static [Requirement] {
+        Benchmark::requirements(self)
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        Benchmark::bytes_per_iteration(self)
+    }
+
+    fn memory_footprint(&self) -> usize {
+        Benchmark::memory_footprint(self)
+    }
+
+    fn disk_footprint(&self) -> usize {
+        Benchmark::disk_footprint(self)
+    }
+
+    fn stops_early(&self) -> bool {
+        Benchmark::stops_early(self)
+    }
+
+    fn run(&self) -> io::Result<BenchmarkResult> {
+        let setup = || self.setup();
+        let iteration = |state: &mut B::State| self.iteration(state);
+        let result = if self.threaded() {
+            benchmark_threaded(self.sampling(), setup, iteration)
+        } else {
+            benchmark(self.sampling(), setup, iteration)
+        };
+        self.teardown();
+        result
+    }
+}
+
+#[derive(Default)]
+pub struct Registry {
+    benchmarks: Vec<Box<dyn DynBenchmark>>,
+}
+
+impl Registry {
+    pub fn register<B: Benchmark + 'static>(&mut self, benchmark: B) {
+        self.benchmarks.push(Box::new(benchmark));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn DynBenchmark> {
+        self.benchmarks.iter().map(|benchmark| benchmark.as_ref())
+    }
+}
+
+// In the order they're run.
+pub fn registry() -> Registry {
+    let mut registry = Registry::default();
+    memory::register(&mut registry);
+    bandwidth::register(&mut registry);
+    copy::register(&mut registry);
+    numa::register(&mut registry);
+    pages::register(&mut registry);
+    faults::register(&mut registry);
+    syscall::register(&mut registry);
+    switch::register(&mut registry);
+    disk::register(&mut registry);
+    network::register(&mut registry);
+    database::register(&mut registry);
+    sort::register(&mut registry);
+    concurrency::register(&mut registry);
+    allocator::register(&mut registry);
+    creation::register(&mut registry);
+    layout::register(&mut registry);
+    hash::register(&mut registry);
+    registry
+}
@@ -0,0 +1,149 @@
+use super::{Benchmark, Category, Registry};
+use crate::{Control, Sampling};
+use std::io;
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+pub fn register(registry: &mut Registry) {
+    registry.register(TcpReadWrite);
+}
+
+const BUF_SIZE: usize = n_kib_bytes!(32) as usize;
+
+fn configure(stream: &TcpStream) {
+    stream.set_nodelay(true).unwrap();
+    stream.set_nonblocking(false).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_millis(1000)))
+        .unwrap();
+    stream
+        .set_write_timeout(Some(Duration::from_millis(1000)))
+        .unwrap();
+}
+
+// This server doesn't support multiple clients. It exits when the client hangs up, which happens
+// when `benchmark` drops the warmup state.
+fn echo_server(listener: TcpListener) {
+    let (mut stream, _) = listener.accept().unwrap();
+    configure(&stream);
+
+    let mut buffer: [u8; BUF_SIZE] = [0; BUF_SIZE];
+
+    loop {
+        match stream.read(&mut buffer) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                // println!("s{}: failed to read, err: {:?}..", i, e);
+                continue;
+            }
+            Ok(0) => return,
+            Ok(n) => {
+                // println!("s{}: read: {}", i, n);
+
+                match stream.write(&buffer[..n]) {
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        // println!("s{}: failed to write", i);
+                        continue;
+                    }
+                    Ok(_n) => {
+                        // println!("s{}: write: {}", i, n);
+                    }
+                    Err(e) => panic!("{}", e),
+                };
+            }
+            Err(e) => panic!("{}", e),
+        };
+
+        // i += 1;
+    }
+}
+
+pub struct TcpReadWrite;
+
+pub struct TcpReadWriteTest {
+    stream: TcpStream,
+    bytes: Vec<u8>,
+    buffer: [u8; BUF_SIZE],
+}
+
+impl Benchmark for TcpReadWrite {
+    type State = TcpReadWriteTest;
+
+    fn name(&self) -> &'static str {
+        "tcp_read_write"
+    }
+
+    fn title(&self) -> &'static str {
+        "Tcp Echo"
+    }
+
+    fn description(&self) -> &'static str {
+        "Sends 32 KiB to an echo server over loopback and reads it back."
+    }
+
+    fn category(&self) -> Category {
+        Category::Network
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["tcp", "loopback"]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        BUF_SIZE
+    }
+
+    fn sampling(&self) -> Sampling {
+        Sampling::PerIteration
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        // Let the kernel pick the port, so the warmup and real run each get their own server.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || echo_server(listener));
+
+        let stream = TcpStream::connect(address).unwrap();
+        configure(&stream);
+
+        let bytes: Vec<u8> = (0..BUF_SIZE).map(|_| rand::random::<u8>()).collect();
+        Ok(TcpReadWriteTest {
+            stream,
+            bytes,
+            buffer: [0; BUF_SIZE],
+        })
+    }
+
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        match test.stream.write(&test.bytes) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                // println!("c: failed to write");
+                return Control::Continue;
+            }
+            Ok(n) => {
+                // println!("c: write: {}", n);
+
+                match test.stream.read(&mut test.buffer[0..n]) {
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        // println!("c: failed to read, err: {:?}..", e);
+                        return Control::Continue;
+                    }
+                    Ok(_n) => {
+                        // println!("c: read: {}\n", n);
+                    }
+                    Err(e) => {
+                        // println!("omgs read! {:?}", e.raw_os_error());
+                        panic!("{}", e);
+                    }
+                };
+            }
+            Err(e) => {
+                // println!("omgs write! {:?}", e.raw_os_error());
+                panic!("{}", e);
+            }
+        };
+
+        Control::Continue
+    }
+}
@@ -0,0 +1,151 @@
+// The bandwidth benchmarks' reads on one core, with the working set bound to the core's own NUMA
+// node or to another one. `report numa` puts them side by side as the cross-node penalty.
+
+use super::bandwidth::{
+    random_setup, read_random, read_sequential, sequential_setup, Pool, Workload, WORKING_SET,
+};
+use super::{Benchmark, Category, Registry, Requirement};
+use crate::{numa, Control, Sampling};
+use core_affinity::CoreId;
+use std::io;
+
+pub fn register(registry: &mut Registry) {
+    for workload in NUMA_WORKLOADS.iter() {
+        for placement in [Placement::Local, Placement::Remote].iter() {
+            registry.register(MemoryNuma {
+                workload: *workload,
+                placement: *placement,
+            });
+        }
+    }
+}
+
+pub const NUMA_WORKLOADS: [Workload; 2] = [Workload::ReadSequential, Workload::ReadRandom];
+
+#[derive(Clone, Copy)]
+pub enum Placement {
+    Local,
+    Remote,
+}
+
+pub fn numa_title(workload: Workload, placement: Placement) -> &'static str {
+    match (workload, placement) {
+        (Workload::ReadRandom, Placement::Local) => "NUMA Random Read, local node",
+        (Workload::ReadRandom, Placement::Remote) => "NUMA Random Read, remote node",
+        (_, Placement::Local) => "NUMA Sequential Read, local node",
+        (_, Placement::Remote) => "NUMA Sequential Read, remote node",
+    }
+}
+
+// mbind(2) can fail to move pages under memory pressure. The error goes back through `Pool::new`
+// rather than panicking in the pool's thread.
+fn bind<T>(memory: &mut [T], node: usize) -> io::Result<()> {
+    numa::bind(memory, node)
+        .map_err(|err| io::Error::new(err.kind(), format!("mbind to node {}: {}", node, err)))
+}
+
+struct MemoryNuma {
+    workload: Workload,
+    placement: Placement,
+}
+
+impl Benchmark for MemoryNuma {
+    type State = Pool;
+
+    fn name(&self) -> &'static str {
+        match (self.workload, self.placement) {
+            (Workload::ReadRandom, Placement::Local) => "memory_numa_read_random_local",
+            (Workload::ReadRandom, Placement::Remote) => "memory_numa_read_random_remote",
+            (_, Placement::Local) => "memory_numa_read_sequential_local",
+            (_, Placement::Remote) => "memory_numa_read_sequential_remote",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        numa_title(self.workload, self.placement)
+    }
+
+    fn description(&self) -> &'static str {
+        match self.placement {
+            Placement::Local => {
+                "Reads 1 GiB from a core on the first node, memory bound to that node."
+            }
+            Placement::Remote => {
+                "Reads 1 GiB from a core on the first node, memory bound to another node."
+            }
+        }
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        match self.workload {
+            Workload::ReadRandom => &["numa", "random", "read"],
+            _ => &["numa", "sequential", "read"],
+        }
+    }
+
+    fn requirements(&self) -> &'static [Requirement] {
+        &[Requirement::Numa]
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        WORKING_SET
+    }
+
+    fn memory_footprint(&self) -> usize {
+        match self.workload {
+            Workload::ReadRandom => WORKING_SET + WORKING_SET / 64 * std::mem::size_of::<usize>(),
+            _ => WORKING_SET,
+        }
+    }
+
+    fn sampling(&self) -> Sampling {
+        Sampling::PerIteration
+    }
+
+    // A pool of one, so the pinning doesn't stick to the thread running the benchmarks.
+    fn threaded(&self) -> bool {
+        true
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let (local, remote) = numa::local_and_remote()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "needs two NUMA nodes"))?;
+        let core = [Some(CoreId { id: local.cpus[0] })];
+        let node = match self.placement {
+            Placement::Local => local.id,
+            Placement::Remote => remote.id,
+        };
+
+        // Only the data is bound. The random read's order is read sequentially and stays on the
+        // local node, where the prefetcher hides it either way.
+        match self.workload {
+            Workload::ReadRandom => Pool::new(
+                &core,
+                move |bytes| {
+                    let mut slice = random_setup(bytes);
+                    bind(&mut slice.vec, node)?;
+                    Ok(slice)
+                },
+                read_random,
+            ),
+            _ => Pool::new(
+                &core,
+                move |bytes| {
+                    let mut slice = sequential_setup(bytes);
+                    bind(&mut slice.vec, node)?;
+                    Ok(slice)
+                },
+                read_sequential,
+            ),
+        }
+    }
+
+    fn iteration(&self, pool: &mut Self::State) -> Control {
+        pool.run();
+        Control::Continue
+    }
+}
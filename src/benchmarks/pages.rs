@@ -0,0 +1,318 @@
+// `memory_read_random` and `memory_read_dependent` over memory we map ourselves, so we choose the
+// page size instead of whatever `/sys/kernel/mm/transparent_hugepage/enabled` happens to say. At 1
+// GiB with 4 KiB pages nearly every read misses the TLB and walks the page tables, with 2 MiB pages
+// the whole working set needs 512 entries. `report pages` puts the variants side by side.
+
+use super::Registry;
+#[cfg(target_os = "linux")]
+use super::{Benchmark, Category, Requirement};
+#[cfg(target_os = "linux")]
+use crate::{black_box, Control};
+#[cfg(target_os = "linux")]
+use rand::seq::SliceRandom;
+#[cfg(target_os = "linux")]
+use rand::thread_rng;
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::io;
+
+const WORKING_SET: usize = n_gib_bytes!(1) as usize;
+
+pub fn register(registry: &mut Registry) {
+    #[cfg(target_os = "linux")]
+    for read in [PageRead::Random, PageRead::Dependent].iter() {
+        for pages in PAGES.iter() {
+            registry.register(MemoryReadPages {
+                read: *read,
+                pages: *pages,
+            });
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Pages {
+    // madvise(MADV_NOHUGEPAGE), 4 KiB pages even when THP is set to `always`.
+    Small,
+    // madvise(MADV_HUGEPAGE), huge pages when khugepaged or the fault path can find them.
+    Transparent,
+    // mmap(MAP_HUGETLB), from the reserved pool in vm.nr_hugepages, so huge pages or nothing.
+    HugeTlb,
+}
+
+pub const PAGES: [Pages; 3] = [Pages::Small, Pages::Transparent, Pages::HugeTlb];
+
+impl Pages {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Pages::Small => "4 KiB pages",
+            Pages::Transparent => "transparent huge pages",
+            Pages::HugeTlb => "hugetlb pages",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum PageRead {
+    Random,
+    Dependent,
+}
+
+pub fn page_read_title(read: PageRead, pages: Pages) -> &'static str {
+    match (read, pages) {
+        (PageRead::Random, Pages::Small) => "Random Read Vec, 4 KiB pages",
+        (PageRead::Random, Pages::Transparent) => "Random Read Vec, transparent huge pages",
+        (PageRead::Random, Pages::HugeTlb) => "Random Read Vec, hugetlb pages",
+        (PageRead::Dependent, Pages::Small) => "Dependent Read Vec, 4 KiB pages",
+        (PageRead::Dependent, Pages::Transparent) => "Dependent Read Vec, transparent huge pages",
+        (PageRead::Dependent, Pages::HugeTlb) => "Dependent Read Vec, hugetlb pages",
+    }
+}
+
+// False when THP is `never`, MADV_HUGEPAGE is ignored then.
+pub fn transparent_huge_pages() -> bool {
+    fs::read_to_string("/sys/kernel/mm/transparent_hugepage/enabled")
+        .is_ok_and(|enabled| !enabled.contains("[never]"))
+}
+
+// Whether the hugetlb pool has room for the working set.
+pub fn huge_tlb_pages() -> bool {
+    match (meminfo("HugePages_Free"), meminfo("Hugepagesize")) {
+        (Some(free), Some(size)) => free * size * 1024 >= WORKING_SET,
+        _ => false,
+    }
+}
+
+// A number from /proc/meminfo, in kB for the sizes and a count for HugePages_*.
+fn meminfo(field: &str) -> Option<usize> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo
+        .lines()
+        .find(|line| line.split(':').next() == Some(field))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+// What a PMD maps, the size THP promotes to.
+#[cfg(target_os = "linux")]
+fn transparent_huge_page_size() -> usize {
+    fs::read_to_string("/sys/kernel/mm/transparent_hugepage/hpage_pmd_size")
+        .ok()
+        .and_then(|size| size.trim().parse().ok())
+        .unwrap_or(n_mib_bytes!(2) as usize)
+}
+
+// An anonymous mapping holding `len` Ts, unmapped on drop.
+#[cfg(target_os = "linux")]
+pub struct Mapping<T> {
+    address: *mut libc::c_void,
+    length: usize,
+    data: *mut T,
+    len: usize,
+}
+
+#[cfg(target_os = "linux")]
+impl<T: Copy> Mapping<T> {
+    // The advice goes in before anything is written, so the first touch already faults in the
+    // pages we asked for.
+    fn new(len: usize, value: T, pages: Pages) -> io::Result<Mapping<T>> {
+        let bytes = len * std::mem::size_of::<T>();
+        let (huge, flags) = match pages {
+            Pages::HugeTlb => (
+                meminfo("Hugepagesize").unwrap_or(2048) * 1024,
+                libc::MAP_HUGETLB,
+            ),
+            _ => (transparent_huge_page_size(), 0),
+        };
+        // hugetlb mappings come aligned, for THP we map a huge page extra to align the start
+        // ourselves, otherwise the first and last bit can't be huge.
+        let length = match pages {
+            Pages::HugeTlb => bytes.div_ceil(huge) * huge,
+            _ => bytes.div_ceil(huge) * huge + huge,
+        };
+
+        let address = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                length,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | flags,
+                -1,
+                0,
+            )
+        };
+        if address == libc::MAP_FAILED {
+            let err = io::Error::last_os_error();
+            return Err(io::Error::new(
+                err.kind(),
+                format!("mmap {} bytes: {}", length, err),
+            ));
+        }
+        let start = (address as usize).div_ceil(huge) * huge;
+        let mapping = Mapping {
+            address,
+            length,
+            data: start as *mut T,
+            len,
+        };
+
+        let advice = match pages {
+            Pages::Small => Some(libc::MADV_NOHUGEPAGE),
+            Pages::Transparent => Some(libc::MADV_HUGEPAGE),
+            Pages::HugeTlb => None,
+        };
+        if let Some(advice) = advice {
+            if unsafe { libc::madvise(address, length, advice) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        for i in 0..len {
+            unsafe { mapping.data.add(i).write(value) };
+        }
+        Ok(mapping)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl<T> std::ops::Deref for Mapping<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.data, self.len) }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl<T> std::ops::DerefMut for Mapping<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.data, self.len) }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl<T> Drop for Mapping<T> {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.address, self.length) };
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub struct MemoryReadPagesTest {
+    vec: Mapping<[u64; 8]>,
+    // Read in order, so it's left to the allocator. Empty for the dependent reads.
+    order: Vec<usize>,
+    i: usize,
+}
+
+#[cfg(target_os = "linux")]
+struct MemoryReadPages {
+    read: PageRead,
+    pages: Pages,
+}
+
+#[cfg(target_os = "linux")]
+impl Benchmark for MemoryReadPages {
+    type State = MemoryReadPagesTest;
+
+    fn name(&self) -> &'static str {
+        match (self.read, self.pages) {
+            (PageRead::Random, Pages::Small) => "memory_read_random_pages_4kib",
+            (PageRead::Random, Pages::Transparent) => "memory_read_random_pages_thp",
+            (PageRead::Random, Pages::HugeTlb) => "memory_read_random_pages_hugetlb",
+            (PageRead::Dependent, Pages::Small) => "memory_read_dependent_pages_4kib",
+            (PageRead::Dependent, Pages::Transparent) => "memory_read_dependent_pages_thp",
+            (PageRead::Dependent, Pages::HugeTlb) => "memory_read_dependent_pages_hugetlb",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        page_read_title(self.read, self.pages)
+    }
+
+    fn description(&self) -> &'static str {
+        match (self.read, self.pages) {
+            (PageRead::Random, Pages::Small) => {
+                "`memory_read_random` over 1 GiB mapped with MADV_NOHUGEPAGE."
+            }
+            (PageRead::Random, Pages::Transparent) => {
+                "`memory_read_random` over 1 GiB mapped with MADV_HUGEPAGE."
+            }
+            (PageRead::Random, Pages::HugeTlb) => {
+                "`memory_read_random` over 1 GiB mapped with MAP_HUGETLB."
+            }
+            (PageRead::Dependent, Pages::Small) => {
+                "`memory_read_dependent` over 1 GiB mapped with MADV_NOHUGEPAGE."
+            }
+            (PageRead::Dependent, Pages::Transparent) => {
+                "`memory_read_dependent` over 1 GiB mapped with MADV_HUGEPAGE."
+            }
+            (PageRead::Dependent, Pages::HugeTlb) => {
+                "`memory_read_dependent` over 1 GiB mapped with MAP_HUGETLB."
+            }
+        }
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        match self.read {
+            PageRead::Random => &["random", "read", "pages"],
+            PageRead::Dependent => &["random", "read", "latency", "pages"],
+        }
+    }
+
+    fn requirements(&self) -> &'static [Requirement] {
+        match self.pages {
+            Pages::Small => &[Requirement::Linux],
+            Pages::Transparent => &[Requirement::Linux, Requirement::TransparentHugePages],
+            Pages::HugeTlb => &[Requirement::Linux, Requirement::HugeTlbPages],
+        }
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        64
+    }
+
+    fn memory_footprint(&self) -> usize {
+        WORKING_SET + WORKING_SET / 64 * std::mem::size_of::<usize>()
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let size_in_elements = WORKING_SET / 64;
+        let mut vec = Mapping::new(size_in_elements, [1, 2, 3, 4, 5, 6, 7, 8], self.pages)?;
+        let mut order: Vec<usize> = (0..size_in_elements).collect();
+        order.shuffle(&mut thread_rng());
+
+        Ok(match self.read {
+            PageRead::Random => MemoryReadPagesTest { vec, order, i: 0 },
+            // The same single random cycle as `memory_read_dependent`.
+            PageRead::Dependent => {
+                for k in 0..size_in_elements {
+                    vec[order[k]][0] = order[(k + 1) % size_in_elements] as u64;
+                }
+                MemoryReadPagesTest {
+                    vec,
+                    i: order[0],
+                    order: Vec::new(),
+                }
+            }
+        })
+    }
+
+    // Starts over at the end, like the sweeps, so every variant runs for the whole window.
+    fn iteration(&self, test: &mut Self::State) -> Control {
+        match self.read {
+            PageRead::Random => {
+                black_box(test.vec[test.order[test.i]]);
+                test.i += 1;
+                if test.i == test.vec.len() {
+                    test.i = 0;
+                }
+            }
+            PageRead::Dependent => test.i = black_box(test.vec[test.i][0]) as usize,
+        }
+        Control::Continue
+    }
+}
@@ -0,0 +1,51 @@
+use super::{Benchmark, Category, Registry};
+use crate::Control;
+use std::io;
+
+pub fn register(registry: &mut Registry) {
+    registry.register(Sort);
+}
+
+const TOTAL_SIZE: usize = n_mib_bytes!(1) as usize;
+
+pub struct Sort;
+
+impl Benchmark for Sort {
+    type State = Vec<u64>;
+
+    fn name(&self) -> &'static str {
+        "sort"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sort"
+    }
+
+    fn description(&self) -> &'static str {
+        "Sorts 1 MiB of random 64-bit integers with `sort_unstable`."
+    }
+
+    fn category(&self) -> Category {
+        Category::Cpu
+    }
+
+    fn bytes_per_iteration(&self) -> usize {
+        TOTAL_SIZE
+    }
+
+    fn memory_footprint(&self) -> usize {
+        TOTAL_SIZE
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let elements = TOTAL_SIZE / 8;
+        let bytes: Vec<u64> = (0..elements).map(|_| rand::random::<u64>()).collect();
+        Ok(bytes)
+    }
+
+    fn iteration(&self, bytes: &mut Self::State) -> Control {
+        bytes.sort_unstable();
+        // Sorting sorted data is a different benchmark, shuffle again.
+        Control::ResetWithSetup
+    }
+}
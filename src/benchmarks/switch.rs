@@ -0,0 +1,407 @@
+// A token bounced between the benchmark's thread and a peer thread or process through a pipe, an
+// eventfd or a futex, with both ends pinned to the same core or to two different ones. On the same
+// core every round trip is two context switches, the number the README's "Context Switch" row is
+// about. Across cores it's two wakeups of a sleeping peer instead. `report switches` has them all.
+
+#[cfg(target_os = "linux")]
+use super::bandwidth::{cores, cpu_set, set_affinity, Pinned};
+use super::Registry;
+#[cfg(target_os = "linux")]
+use super::{Benchmark, Category, Requirement};
+#[cfg(target_os = "linux")]
+use crate::Control;
+#[cfg(target_os = "linux")]
+use std::io;
+#[cfg(target_os = "linux")]
+use std::sync::atomic::{AtomicU32, Ordering};
+#[cfg(target_os = "linux")]
+use std::thread;
+
+pub fn register(registry: &mut Registry) {
+    #[cfg(target_os = "linux")]
+    for mechanism in MECHANISMS.iter() {
+        for peer in [Peer::Thread, Peer::Process].iter() {
+            for cores in [SwitchCores::Same, SwitchCores::Other].iter() {
+                registry.register(ContextSwitch::new(*mechanism, *peer, *cores));
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Mechanism {
+    // A pipe each way, one byte per message.
+    Pipe,
+    // An eventfd each way, the counter is the message.
+    Eventfd,
+    // One word in shared memory, FUTEX_WAIT until the other side flips it and FUTEX_WAKEs us.
+    Futex,
+}
+
+pub const MECHANISMS: [Mechanism; 3] = [Mechanism::Pipe, Mechanism::Eventfd, Mechanism::Futex];
+
+impl Mechanism {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mechanism::Pipe => "pipe",
+            Mechanism::Eventfd => "eventfd",
+            Mechanism::Futex => "futex",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Peer {
+    Thread,
+    // fork(2)ed, so switching to it also switches address spaces.
+    Process,
+}
+
+impl Peer {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Peer::Thread => "thread",
+            Peer::Process => "process",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum SwitchCores {
+    Same,
+    Other,
+}
+
+impl SwitchCores {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SwitchCores::Same => "same core",
+            SwitchCores::Other => "other core",
+        }
+    }
+}
+
+pub fn switch_title(mechanism: Mechanism, peer: Peer, cores: SwitchCores) -> String {
+    format!(
+        "Context switch, {} to a {} on the {}",
+        mechanism.as_str(),
+        peer.as_str(),
+        cores.as_str()
+    )
+}
+
+// Messages: a round trip is `PING` there and back, `STOP` tells the peer to exit.
+#[cfg(target_os = "linux")]
+const PING: u64 = 1;
+#[cfg(target_os = "linux")]
+const STOP: u64 = 2;
+
+// Both directions of one mechanism. Only file descriptors and a pointer into a MAP_SHARED page, so
+// it works the same in a thread and in a forked child, which only makes raw syscalls.
+#[cfg(target_os = "linux")]
+#[derive(Clone, Copy)]
+struct Channel {
+    mechanism: Mechanism,
+    // (read, write) ends towards the peer and back, the same fd twice for an eventfd.
+    to_peer: (libc::c_int, libc::c_int),
+    from_peer: (libc::c_int, libc::c_int),
+    word: *const AtomicU32,
+}
+
+#[cfg(target_os = "linux")]
+unsafe impl Send for Channel {}
+
+#[cfg(target_os = "linux")]
+impl Channel {
+    fn new(mechanism: Mechanism) -> Channel {
+        let pair = || match mechanism {
+            Mechanism::Pipe => {
+                let mut fds = [0; 2];
+                assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0, "pipe(2)");
+                (fds[0], fds[1])
+            }
+            Mechanism::Eventfd => {
+                let fd = unsafe { libc::eventfd(0, 0) };
+                assert!(fd >= 0, "eventfd(2)");
+                (fd, fd)
+            }
+            Mechanism::Futex => (-1, -1),
+        };
+        let word = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                std::mem::size_of::<AtomicU32>(),
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(word, libc::MAP_FAILED, "mmap(2)");
+
+        Channel {
+            mechanism,
+            to_peer: pair(),
+            from_peer: pair(),
+            word: word as *const AtomicU32,
+        }
+    }
+
+    fn word(&self) -> &AtomicU32 {
+        unsafe { &*self.word }
+    }
+
+    // Not FUTEX_PRIVATE_FLAG, the forked peer waits on the same word through its own mapping.
+    fn futex_wait(&self, expected: u32) {
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                self.word,
+                libc::FUTEX_WAIT,
+                expected,
+                std::ptr::null::<libc::timespec>(),
+            )
+        };
+    }
+
+    fn futex_wake(&self) {
+        unsafe { libc::syscall(libc::SYS_futex, self.word, libc::FUTEX_WAKE, 1) };
+    }
+
+    fn send(&self, fd: libc::c_int, message: u64) {
+        match self.mechanism {
+            Mechanism::Pipe => unsafe {
+                libc::write(fd, &(message as u8) as *const u8 as *const libc::c_void, 1)
+            },
+            _ => unsafe { libc::write(fd, &message as *const u64 as *const libc::c_void, 8) },
+        };
+    }
+
+    fn receive(&self, fd: libc::c_int) -> u64 {
+        match self.mechanism {
+            Mechanism::Pipe => {
+                let mut message = 0u8;
+                unsafe { libc::read(fd, &mut message as *mut u8 as *mut libc::c_void, 1) };
+                message as u64
+            }
+            _ => {
+                let mut message = 0u64;
+                unsafe { libc::read(fd, &mut message as *mut u64 as *mut libc::c_void, 8) };
+                message
+            }
+        }
+    }
+
+    // Our side: hand the token over and sleep until it's back.
+    fn round_trip(&self) {
+        match self.mechanism {
+            Mechanism::Futex => {
+                self.word().store(PING as u32, Ordering::SeqCst);
+                self.futex_wake();
+                while self.word().load(Ordering::SeqCst) == PING as u32 {
+                    self.futex_wait(PING as u32);
+                }
+            }
+            _ => {
+                self.send(self.to_peer.1, PING);
+                self.receive(self.from_peer.0);
+            }
+        }
+    }
+
+    // The peer's side, until it's told to stop.
+    fn serve(&self) {
+        match self.mechanism {
+            Mechanism::Futex => loop {
+                while self.word().load(Ordering::SeqCst) == 0 {
+                    self.futex_wait(0);
+                }
+                if self.word().load(Ordering::SeqCst) == STOP as u32 {
+                    return;
+                }
+                self.word().store(0, Ordering::SeqCst);
+                self.futex_wake();
+            },
+            _ => {
+                while self.receive(self.to_peer.0) == PING {
+                    self.send(self.from_peer.1, PING);
+                }
+            }
+        }
+    }
+
+    fn stop(&self) {
+        match self.mechanism {
+            Mechanism::Futex => {
+                self.word().store(STOP as u32, Ordering::SeqCst);
+                self.futex_wake();
+            }
+            _ => self.send(self.to_peer.1, STOP),
+        }
+    }
+
+    fn close(&self) {
+        let mut fds = vec![
+            self.to_peer.0,
+            self.to_peer.1,
+            self.from_peer.0,
+            self.from_peer.1,
+        ];
+        fds.sort_unstable();
+        fds.dedup();
+        for fd in fds.into_iter().filter(|fd| *fd >= 0) {
+            unsafe { libc::close(fd) };
+        }
+        unsafe {
+            libc::munmap(
+                self.word as *mut libc::c_void,
+                std::mem::size_of::<AtomicU32>(),
+            )
+        };
+    }
+}
+
+#[cfg(target_os = "linux")]
+enum Running {
+    Thread(thread::JoinHandle<()>),
+    Process(libc::pid_t),
+}
+
+// The benchmark's thread is pinned to `ours` for as long as this lives.
+#[cfg(target_os = "linux")]
+pub struct SwitchPeer {
+    channel: Channel,
+    running: Option<Running>,
+    // Dropped after the peer has stopped.
+    _pinned: Pinned,
+}
+
+#[cfg(target_os = "linux")]
+impl SwitchPeer {
+    fn new(mechanism: Mechanism, peer: Peer, ours: usize, theirs: usize) -> SwitchPeer {
+        let pinned = Pinned::new(ours);
+
+        let channel = Channel::new(mechanism);
+        let theirs = cpu_set(theirs);
+        let running = match peer {
+            Peer::Thread => Running::Thread(thread::spawn(move || {
+                set_affinity(&theirs);
+                channel.serve();
+            })),
+            Peer::Process => match unsafe { libc::fork() } {
+                -1 => panic!("fork(2): {}", std::io::Error::last_os_error()),
+                0 => {
+                    set_affinity(&theirs);
+                    channel.serve();
+                    unsafe { libc::_exit(0) };
+                }
+                pid => Running::Process(pid),
+            },
+        };
+
+        SwitchPeer {
+            channel,
+            running: Some(running),
+            _pinned: pinned,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for SwitchPeer {
+    fn drop(&mut self) {
+        self.channel.stop();
+        match self.running.take() {
+            Some(Running::Thread(thread)) => thread.join().unwrap(),
+            Some(Running::Process(pid)) => unsafe {
+                libc::waitpid(pid, std::ptr::null_mut(), 0);
+            },
+            None => {}
+        }
+        self.channel.close();
+    }
+}
+
+#[cfg(target_os = "linux")]
+struct ContextSwitch {
+    mechanism: Mechanism,
+    peer: Peer,
+    cores: SwitchCores,
+    name: &'static str,
+    title: &'static str,
+}
+
+#[cfg(target_os = "linux")]
+impl ContextSwitch {
+    fn new(mechanism: Mechanism, peer: Peer, cores: SwitchCores) -> ContextSwitch {
+        // e.g. context_switch_futex_process_same_core.
+        let name = format!(
+            "context_switch_{}_{}_{}",
+            mechanism.as_str(),
+            peer.as_str(),
+            cores.as_str().replace(' ', "_")
+        );
+        ContextSwitch {
+            mechanism,
+            peer,
+            cores,
+            name: Box::leak(name.into_boxed_str()),
+            title: Box::leak(switch_title(mechanism, peer, cores).into_boxed_str()),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Benchmark for ContextSwitch {
+    type State = SwitchPeer;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn description(&self) -> &'static str {
+        match self.cores {
+            SwitchCores::Same => "A round trip to a peer pinned to our core, two context switches.",
+            SwitchCores::Other => "A round trip to a peer pinned to another core, two wakeups.",
+        }
+    }
+
+    fn category(&self) -> Category {
+        Category::Syscall
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["context-switch", "latency", "threaded"]
+    }
+
+    fn requirements(&self) -> &'static [Requirement] {
+        match self.cores {
+            SwitchCores::Same => &[Requirement::Linux],
+            SwitchCores::Other => &[Requirement::Linux, Requirement::MultipleCores],
+        }
+    }
+
+    // Pinned to the first core we're allowed on, and the second for the peer when it's elsewhere.
+    fn threaded(&self) -> bool {
+        true
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        let cores: Vec<usize> = cores().into_iter().flatten().map(|core| core.id).collect();
+        let ours = cores.first().copied().unwrap_or(0);
+        let theirs = match self.cores {
+            SwitchCores::Same => ours,
+            SwitchCores::Other => cores.get(1).copied().unwrap_or(ours),
+        };
+        Ok(SwitchPeer::new(self.mechanism, self.peer, ours, theirs))
+    }
+
+    fn iteration(&self, peer: &mut Self::State) -> Control {
+        peer.channel.round_trip();
+        Control::Continue
+    }
+}
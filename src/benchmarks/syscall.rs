@@ -0,0 +1,335 @@
+use super::{Benchmark, Category, Registry, Requirement};
+use crate::{black_box, Control};
+use std::fs;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::process;
+use std::time::SystemTime;
+
+pub fn register(registry: &mut Registry) {
+    registry.register(SyscallGetpid);
+    registry.register(SyscallTime);
+    registry.register(SyscallGetrusage);
+    registry.register(SyscallStat);
+    register_matrix(registry);
+}
+
+// this comes from the auxilirary vector on some OSes, making this not do a syscall.
+// on the linux kernel I've been testing on, it does do a syscall. on darwin, it doesn't.
+pub struct SyscallGetpid;
+
+impl Benchmark for SyscallGetpid {
+    type State = ();
+
+    fn name(&self) -> &'static str {
+        "syscall_getpid"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sycall getpid(2)"
+    }
+
+    fn description(&self) -> &'static str {
+        "getpid(2), a syscall on Linux but answered from user space on some OSes."
+    }
+
+    fn category(&self) -> Category {
+        Category::Syscall
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(())
+    }
+
+    fn iteration(&self, _test: &mut Self::State) -> Control {
+        black_box(process::id());
+        Control::Continue
+    }
+}
+
+// this is available in user-space memory (depending on libc) and often doesn't result in a sycall.
+pub struct SyscallTime;
+
+impl Benchmark for SyscallTime {
+    type State = ();
+
+    fn name(&self) -> &'static str {
+        "syscall_time"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sycall gettimeofday(2)"
+    }
+
+    fn description(&self) -> &'static str {
+        "gettimeofday(2) through `SystemTime::now`, usually served by the vDSO without a syscall."
+    }
+
+    fn category(&self) -> Category {
+        Category::Syscall
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["vdso"]
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(())
+    }
+
+    fn iteration(&self, _test: &mut Self::State) -> Control {
+        black_box(SystemTime::now());
+        Control::Continue
+    }
+}
+
+// syscall, can't be optimized out
+pub struct SyscallGetrusage;
+
+impl Benchmark for SyscallGetrusage {
+    type State = Box<libc::rusage>;
+
+    fn name(&self) -> &'static str {
+        "syscall_getrusage"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sycall getrusage(2)"
+    }
+
+    fn description(&self) -> &'static str {
+        "getrusage(2), which always enters the kernel."
+    }
+
+    fn category(&self) -> Category {
+        Category::Syscall
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        Ok(Box::new(unsafe { std::mem::zeroed() }))
+    }
+
+    fn iteration(&self, rusage: &mut Self::State) -> Control {
+        unsafe {
+            libc::getrusage(0, rusage.as_mut());
+        }
+        Control::Continue
+    }
+}
+
+// syscall, can't be optimized out
+pub struct SyscallStat;
+
+impl Benchmark for SyscallStat {
+    type State = fs::File;
+
+    fn name(&self) -> &'static str {
+        "syscall_stat"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sycall stat(2)"
+    }
+
+    fn description(&self) -> &'static str {
+        "fstat(2) on an open handle to /tmp."
+    }
+
+    fn category(&self) -> Category {
+        Category::Syscall
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        fs::File::open("/tmp")
+    }
+
+    fn iteration(&self, f: &mut Self::State) -> Control {
+        let metadata = f.metadata().unwrap();
+        black_box(metadata);
+        Control::Continue
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Syscall {
+    Getpid,
+    ClockGettime,
+    Getrusage,
+    // read(2) of 0 bytes from /dev/null, an fd lookup and nothing else.
+    ReadZero,
+    // A number no kernel has, so all we pay for is getting in and out with ENOSYS.
+    Invalid,
+}
+
+pub const SYSCALLS: [Syscall; 5] = [
+    Syscall::Getpid,
+    Syscall::ClockGettime,
+    Syscall::Getrusage,
+    Syscall::ReadZero,
+    Syscall::Invalid,
+];
+
+impl Syscall {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Syscall::Getpid => "getpid",
+            Syscall::ClockGettime => "clock_gettime",
+            Syscall::Getrusage => "getrusage",
+            Syscall::ReadZero => "read",
+            Syscall::Invalid => "invalid",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum SyscallPath {
+    // Whatever libc does for the call, which for clock_gettime is the vDSO without entering the
+    // kernel, and on other OSes may be a cached getpid.
+    Libc,
+    // libc::syscall(SYS_...), which always traps into the kernel.
+    Raw,
+}
+
+impl SyscallPath {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SyscallPath::Libc => "libc",
+            SyscallPath::Raw => "raw",
+        }
+    }
+}
+
+// What the results are reported as, `report syscalls` and the README's "System Call" row look
+// them up by it.
+pub fn syscall_title(call: Syscall, path: SyscallPath) -> String {
+    match path {
+        SyscallPath::Libc => format!("Syscall {}(2) through libc", call.as_str()),
+        SyscallPath::Raw => format!("Syscall {}(2) raw", call.as_str()),
+    }
+}
+
+fn register_matrix(registry: &mut Registry) {
+    for call in SYSCALLS.iter() {
+        // There's no libc function for a syscall that doesn't exist.
+        if !matches!(call, Syscall::Invalid) {
+            registry.register(SyscallMatrix::new(*call, SyscallPath::Libc));
+        }
+        #[cfg(target_os = "linux")]
+        registry.register(SyscallMatrix::new(*call, SyscallPath::Raw));
+    }
+}
+
+pub struct SyscallMatrix {
+    call: Syscall,
+    path: SyscallPath,
+    name: &'static str,
+    title: &'static str,
+}
+
+impl SyscallMatrix {
+    fn new(call: Syscall, path: SyscallPath) -> SyscallMatrix {
+        // e.g. syscall_raw_clock_gettime.
+        let name = format!("syscall_{}_{}", path.as_str(), call.as_str());
+        SyscallMatrix {
+            call,
+            path,
+            name: Box::leak(name.into_boxed_str()),
+            title: Box::leak(syscall_title(call, path).into_boxed_str()),
+        }
+    }
+}
+
+impl Benchmark for SyscallMatrix {
+    type State = fs::File;
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn description(&self) -> &'static str {
+        match self.path {
+            SyscallPath::Libc => {
+                "The libc wrapper, which may not enter the kernel (vDSO, caching)."
+            }
+            SyscallPath::Raw => {
+                "libc::syscall(2) with the syscall number, always a kernel transition."
+            }
+        }
+    }
+
+    fn category(&self) -> Category {
+        Category::Syscall
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        match (self.call, self.path) {
+            (Syscall::ClockGettime, SyscallPath::Libc) => &["vdso"],
+            (_, SyscallPath::Libc) => &["libc"],
+            (_, SyscallPath::Raw) => &["raw"],
+        }
+    }
+
+    fn requirements(&self) -> &'static [Requirement] {
+        match self.path {
+            SyscallPath::Libc => &[],
+            SyscallPath::Raw => &[Requirement::Linux],
+        }
+    }
+
+    fn setup(&self) -> io::Result<Self::State> {
+        fs::File::open("/dev/null")
+    }
+
+    fn iteration(&self, file: &mut Self::State) -> Control {
+        let fd = file.as_raw_fd();
+        let mut timespec: libc::timespec = unsafe { std::mem::zeroed() };
+        let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+        let mut buffer = [0u8; 1];
+        let buffer = buffer.as_mut_ptr() as *mut libc::c_void;
+
+        match self.path {
+            SyscallPath::Libc => black_box(unsafe {
+                match self.call {
+                    Syscall::Getpid => libc::getpid() as libc::c_long,
+                    Syscall::ClockGettime => {
+                        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut timespec) as libc::c_long
+                    }
+                    Syscall::Getrusage => {
+                        libc::getrusage(libc::RUSAGE_SELF, &mut rusage) as libc::c_long
+                    }
+                    Syscall::ReadZero => libc::read(fd, buffer, 0) as libc::c_long,
+                    Syscall::Invalid => unreachable!("not registered"),
+                }
+            }),
+            #[cfg(target_os = "linux")]
+            SyscallPath::Raw => black_box(unsafe {
+                match self.call {
+                    Syscall::Getpid => libc::syscall(libc::SYS_getpid),
+                    Syscall::ClockGettime => libc::syscall(
+                        libc::SYS_clock_gettime,
+                        libc::CLOCK_MONOTONIC,
+                        &mut timespec,
+                    ),
+                    Syscall::Getrusage => {
+                        libc::syscall(libc::SYS_getrusage, libc::RUSAGE_SELF, &mut rusage)
+                    }
+                    Syscall::ReadZero => libc::syscall(libc::SYS_read, fd, buffer, 0),
+                    Syscall::Invalid => libc::syscall(INVALID_SYSCALL),
+                }
+            }),
+            #[cfg(not(target_os = "linux"))]
+            SyscallPath::Raw => unreachable!("raw syscalls are only registered on Linux"),
+        };
+        black_box(timespec);
+        black_box(rusage);
+        Control::Continue
+    }
+}
+
+// Well past the last syscall number on every architecture we run on.
+#[cfg(target_os = "linux")]
+const INVALID_SYSCALL: libc::c_long = 100_000;
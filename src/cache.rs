@@ -0,0 +1,114 @@
+// The CPU's data caches as the OS reports them, to check what the memory benchmarks find against.
+
+#[derive(Clone, Debug)]
+pub struct CacheLevel {
+    pub level: u32,
+    pub size: usize,
+}
+
+impl CacheLevel {
+    pub fn name(&self) -> String {
+        format!("L{}", self.level)
+    }
+}
+
+// Data and unified caches of cpu0, smallest first. Empty when the OS doesn't tell us.
+#[cfg(target_os = "linux")]
+pub fn levels() -> Vec<CacheLevel> {
+    use std::fs;
+
+    let mut levels = Vec::new();
+    let entries = match fs::read_dir("/sys/devices/system/cpu/cpu0/cache") {
+        Ok(entries) => entries,
+        Err(_) => return levels,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let read = |file: &str| fs::read_to_string(path.join(file)).ok();
+        let (level, kind, size) = match (read("level"), read("type"), read("size")) {
+            (Some(level), Some(kind), Some(size)) => (level, kind, size),
+            _ => continue,
+        };
+        if kind.trim() == "Instruction" {
+            continue;
+        }
+
+        if let (Ok(level), Some(size)) = (level.trim().parse(), parse_size(size.trim())) {
+            levels.push(CacheLevel { level, size });
+        }
+    }
+
+    levels.sort_by_key(|cache| cache.level);
+    levels
+}
+
+#[cfg(target_os = "macos")]
+pub fn levels() -> Vec<CacheLevel> {
+    let sysctl = |name: &str| {
+        let output = std::process::Command::new("sysctl")
+            .arg("-n")
+            .arg(name)
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    };
+
+    [
+        (1, "hw.l1dcachesize"),
+        (2, "hw.l2cachesize"),
+        (3, "hw.l3cachesize"),
+    ]
+    .iter()
+    .filter_map(|(level, name)| {
+        sysctl(name)
+            .filter(|size| *size > 0)
+            .map(|size| CacheLevel {
+                level: *level,
+                size,
+            })
+    })
+    .collect()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn levels() -> Vec<CacheLevel> {
+    Vec::new()
+}
+
+// sysfs writes sizes as "48K", "2048K" or "36M".
+#[cfg(target_os = "linux")]
+fn parse_size(size: &str) -> Option<usize> {
+    let (number, multiplier) = match size.chars().last()? {
+        'K' => (&size[..size.len() - 1], 1024),
+        'M' => (&size[..size.len() - 1], 1024 * 1024),
+        'G' => (&size[..size.len() - 1], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    number
+        .parse::<usize>()
+        .ok()
+        .map(|number| number * multiplier)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sysfs_sizes() {
+        let cases = [
+            ("48K", Some(48 * 1024)),
+            ("2048K", Some(2 * 1024 * 1024)),
+            ("36M", Some(36 * 1024 * 1024)),
+            ("1G", Some(1024 * 1024 * 1024)),
+            ("512", Some(512)),
+            ("", None),
+            ("K", None),
+            ("12X", None),
+        ];
+        for (size, bytes) in cases.iter() {
+            assert_eq!(parse_size(size), *bytes, "{:?}", size);
+        }
+    }
+}
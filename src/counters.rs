@@ -0,0 +1,285 @@
+// Cycle and hardware event counting for `benchmark`.
+//
+// On x86_64 we always have the TSC through `rdtscp`. It ticks at a constant reference frequency
+// rather than the actual core clock, so it's only "cycles" when turbo is off (which `./run` does).
+// On Linux we additionally try `perf_event_open(2)` for real core cycles, instructions, cache
+// misses, branch misses and dTLB load misses. That needs `kernel.perf_event_paranoid` <= 2 (<= 1
+// to include kernel time) and a PMU, which many VMs don't expose, so everything here degrades to
+// `None`.
+
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::__rdtscp;
+#[cfg(target_arch = "x86_64")]
+use std::sync::OnceLock;
+#[cfg(target_arch = "x86_64")]
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Counts {
+    pub cycles: Option<u64>,
+    pub instructions: Option<u64>,
+    pub cache_misses: Option<u64>,
+    pub branch_misses: Option<u64>,
+    // Loads that missed the TLB and walked the page tables, what huge pages are meant to save.
+    pub dtlb_load_misses: Option<u64>,
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+pub fn rdtscp() -> Option<u64> {
+    let mut aux = 0u32;
+    // rdtscp waits for prior instructions to retire, so the work we're timing can't leak past it.
+    Some(unsafe { __rdtscp(&mut aux) })
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+#[inline(always)]
+pub fn rdtscp() -> Option<u64> {
+    None
+}
+
+// TSC ticks per second, measured once against the monotonic clock.
+#[cfg(target_arch = "x86_64")]
+pub fn tsc_hz() -> Option<f64> {
+    static TSC_HZ: OnceLock<f64> = OnceLock::new();
+    Some(*TSC_HZ.get_or_init(|| {
+        // Spin rather than sleep so we don't calibrate across a deep C-state.
+        let calibration = Duration::from_millis(50);
+        let instant = Instant::now();
+        let before = rdtscp().unwrap();
+        while instant.elapsed() < calibration {}
+        let after = rdtscp().unwrap();
+        (after - before) as f64 / instant.elapsed().as_secs_f64()
+    }))
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub fn tsc_hz() -> Option<f64> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+mod perf {
+    use super::Counts;
+    use std::io::Error;
+    use std::mem::size_of;
+
+    const PERF_TYPE_HARDWARE: u32 = 0;
+    const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+    const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+    const PERF_COUNT_HW_CACHE_MISSES: u64 = 3;
+    const PERF_COUNT_HW_BRANCH_MISSES: u64 = 5;
+
+    // Cache events are the cache, the operation and the result, a byte each.
+    const PERF_TYPE_HW_CACHE: u32 = 3;
+    const PERF_COUNT_HW_CACHE_DTLB: u64 = 3;
+    const PERF_COUNT_HW_CACHE_OP_READ: u64 = 0;
+    const PERF_COUNT_HW_CACHE_RESULT_MISS: u64 = 1;
+    const DTLB_LOAD_MISSES: u64 = PERF_COUNT_HW_CACHE_DTLB
+        | PERF_COUNT_HW_CACHE_OP_READ << 8
+        | PERF_COUNT_HW_CACHE_RESULT_MISS << 16;
+
+    const PERF_FORMAT_TOTAL_TIME_ENABLED: u64 = 1 << 0;
+    const PERF_FORMAT_TOTAL_TIME_RUNNING: u64 = 1 << 1;
+
+    // Bits of the `flags` bitfield in `perf_event_attr`.
+    const FLAG_DISABLED: u64 = 1 << 0;
+    const FLAG_INHERIT: u64 = 1 << 1;
+    const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+    const FLAG_EXCLUDE_HV: u64 = 1 << 6;
+
+    const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+    const PERF_EVENT_IOC_DISABLE: libc::c_ulong = 0x2401;
+    const PERF_EVENT_IOC_RESET: libc::c_ulong = 0x2403;
+
+    // `struct perf_event_attr` up to PERF_ATTR_SIZE_VER5, libc doesn't define it for us.
+    #[repr(C)]
+    #[derive(Default)]
+    struct PerfEventAttr {
+        type_: u32,
+        size: u32,
+        config: u64,
+        sample_period: u64,
+        sample_type: u64,
+        read_format: u64,
+        flags: u64,
+        wakeup_events: u32,
+        bp_type: u32,
+        config1: u64,
+        config2: u64,
+        branch_sample_type: u64,
+        sample_regs_user: u64,
+        sample_stack_user: u32,
+        clockid: i32,
+        sample_regs_intr: u64,
+        aux_watermark: u32,
+        sample_max_stack: u16,
+        reserved: u16,
+    }
+
+    struct Counter {
+        fd: libc::c_int,
+    }
+
+    impl Counter {
+        fn open(type_: u32, config: u64) -> Result<Counter, Error> {
+            // Count the kernel too when we're allowed to, syscall and disk benchmarks spend most
+            // of their time there. Otherwise fall back to user space only.
+            Counter::open_with(type_, config, 0)
+                .or_else(|_| Counter::open_with(type_, config, FLAG_EXCLUDE_KERNEL))
+        }
+
+        fn open_with(type_: u32, config: u64, extra_flags: u64) -> Result<Counter, Error> {
+            let attr = PerfEventAttr {
+                type_,
+                size: size_of::<PerfEventAttr>() as u32,
+                config,
+                read_format: PERF_FORMAT_TOTAL_TIME_ENABLED | PERF_FORMAT_TOTAL_TIME_RUNNING,
+                // Inherit, so threads and processes an iteration starts are counted once they exit.
+                // Threads started before the counters were opened aren't, which is why
+                // `benchmark_threaded` doesn't open them.
+                flags: FLAG_DISABLED | FLAG_INHERIT | FLAG_EXCLUDE_HV | extra_flags,
+                ..Default::default()
+            };
+
+            // This thread, any CPU, no group, no flags.
+            let fd = unsafe {
+                libc::syscall(
+                    libc::SYS_perf_event_open,
+                    &attr as *const PerfEventAttr,
+                    0,
+                    -1,
+                    -1,
+                    0,
+                )
+            };
+            if fd < 0 {
+                return Err(Error::last_os_error());
+            }
+
+            Ok(Counter {
+                fd: fd as libc::c_int,
+            })
+        }
+
+        fn ioctl(&self, request: libc::c_ulong) {
+            unsafe {
+                libc::ioctl(self.fd, request as _, 0);
+            }
+        }
+
+        // The kernel multiplexes counters when there are more events than hardware counters, so
+        // scale by how long this one was actually scheduled.
+        fn read(&self) -> Option<u64> {
+            let mut values = [0u64; 3];
+            let size = size_of::<[u64; 3]>();
+            let n = unsafe { libc::read(self.fd, values.as_mut_ptr() as *mut libc::c_void, size) };
+            let [value, enabled, running] = values;
+            if n as usize != size || running == 0 {
+                return None;
+            }
+
+            Some((value as f64 * enabled as f64 / running as f64) as u64)
+        }
+    }
+
+    impl Drop for Counter {
+        fn drop(&mut self) {
+            unsafe {
+                libc::close(self.fd);
+            }
+        }
+    }
+
+    pub struct Counters {
+        cycles: Option<Counter>,
+        instructions: Option<Counter>,
+        cache_misses: Option<Counter>,
+        branch_misses: Option<Counter>,
+        dtlb_load_misses: Option<Counter>,
+    }
+
+    impl Counters {
+        pub fn open() -> Option<Counters> {
+            let counters = Counters {
+                cycles: Counter::open(PERF_TYPE_HARDWARE, PERF_COUNT_HW_CPU_CYCLES).ok(),
+                instructions: Counter::open(PERF_TYPE_HARDWARE, PERF_COUNT_HW_INSTRUCTIONS).ok(),
+                cache_misses: Counter::open(PERF_TYPE_HARDWARE, PERF_COUNT_HW_CACHE_MISSES).ok(),
+                branch_misses: Counter::open(PERF_TYPE_HARDWARE, PERF_COUNT_HW_BRANCH_MISSES).ok(),
+                dtlb_load_misses: Counter::open(PERF_TYPE_HW_CACHE, DTLB_LOAD_MISSES).ok(),
+            };
+
+            if counters.all().iter().all(|counter| counter.is_none()) {
+                return None;
+            }
+            Some(counters)
+        }
+
+        fn all(&self) -> [&Option<Counter>; 5] {
+            [
+                &self.cycles,
+                &self.instructions,
+                &self.cache_misses,
+                &self.branch_misses,
+                &self.dtlb_load_misses,
+            ]
+        }
+
+        pub fn start(&self) {
+            for counter in self.all().iter().copied().flatten() {
+                counter.ioctl(PERF_EVENT_IOC_RESET);
+                counter.ioctl(PERF_EVENT_IOC_ENABLE);
+            }
+        }
+
+        // Keeps what's been counted so far, for leaving work like `setup` out of a measurement.
+        pub fn pause(&self) {
+            for counter in self.all().iter().copied().flatten() {
+                counter.ioctl(PERF_EVENT_IOC_DISABLE);
+            }
+        }
+
+        pub fn resume(&self) {
+            for counter in self.all().iter().copied().flatten() {
+                counter.ioctl(PERF_EVENT_IOC_ENABLE);
+            }
+        }
+
+        pub fn stop(&self) -> Counts {
+            for counter in self.all().iter().copied().flatten() {
+                counter.ioctl(PERF_EVENT_IOC_DISABLE);
+            }
+
+            Counts {
+                cycles: self.cycles.as_ref().and_then(Counter::read),
+                instructions: self.instructions.as_ref().and_then(Counter::read),
+                cache_misses: self.cache_misses.as_ref().and_then(Counter::read),
+                branch_misses: self.branch_misses.as_ref().and_then(Counter::read),
+                dtlb_load_misses: self.dtlb_load_misses.as_ref().and_then(Counter::read),
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use perf::Counters;
+
+#[cfg(not(target_os = "linux"))]
+pub struct Counters;
+
+#[cfg(not(target_os = "linux"))]
+impl Counters {
+    pub fn open() -> Option<Counters> {
+        None
+    }
+
+    pub fn start(&self) {}
+
+    pub fn pause(&self) {}
+
+    pub fn resume(&self) {}
+
+    pub fn stop(&self) -> Counts {
+        Counts::default()
+    }
+}
@@ -0,0 +1,953 @@
+// The harness behind the `napkin-math` binary, so other tools can run the benchmarks or time their
+// own code with the same warmup, sampling and counters. `main.rs` is only the command line.
+
+#[macro_use]
+extern crate byte_unit;
+use std::fmt::Write as FmtWrite;
+
+extern crate libc;
+
+// #[cfg(target_os = "linux")]
+// use libc::posix_fadvise;
+
+use std::sync::OnceLock;
+
+// #[cfg(target_os = "linux")]
+// use rio::{Rio, Uring};
+
+#[cfg(target_os = "linux")]
+use std::os::unix::io::*;
+
+pub mod baseline;
+pub mod benchmarks;
+pub mod cache;
+pub mod counters;
+pub mod numa;
+pub mod report;
+use baseline::{Baseline, Comparison};
+use counters::{Counters, Counts};
+
+static DEFAULT_FILE_NAME: &str = "/tmp/napkin.txt";
+
+pub fn benchmark_file_name() -> String {
+    std::env::var("NAPKIN_BENCH_FILE").unwrap_or_else(|_| String::from(DEFAULT_FILE_NAME))
+}
+
+#[cfg(target_os = "linux")]
+pub fn drop_file_page_cache(file: &std::fs::File) {
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn drop_file_page_cache(_file: &std::fs::File) {}
+
+// https://ark.intel.com/content/www/us/en/ark/products/97185/intel-core-i7-7700hq-processor-6m-cache-up-to-3-80-ghz.html
+// https://en.wikichip.org/wiki/intel/core_i7/i7-7700hq
+//
+// Single: 17.88 GiB/s
+// Dual:   37.5 GB/s
+//
+// L1: 32 KiB
+// L2: 262 KiB
+// L3: 6 MiB
+//
+// sysctl -a | grep cache <---
+use byte_unit::Byte;
+// use failure::Error;
+use num_format::{Locale, ToFormattedString};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::mem::forget;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// #[cfg(target_arch = "x86_64")]
+// use std::arch::x86_64::*;
+
+// TODO: use this instead
+// from bencher::black_box, avoid compiler dead-code optimizations.
+pub fn black_box<T>(dummy: T) -> T {
+    unsafe {
+        let ret = ptr::read_volatile(&dummy);
+        forget(dummy);
+        ret
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+// Set once from `--output` in `main`, read by `print_results` so the benchmark functions don't all
+// need to thread it through.
+pub static OUTPUT_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+fn output_format() -> OutputFormat {
+    *OUTPUT_FORMAT.get().unwrap_or(&OutputFormat::Text)
+}
+
+// How long `benchmark` spends in each phase. Defaults are what we've always used, CI can shorten
+// them for a smoke pass and the perf lab can lengthen them for stabler numbers.
+#[derive(Clone, Copy, Debug)]
+pub struct Timings {
+    pub warmup: Duration,
+    pub measure: Duration,
+    pub cooldown: Duration,
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Timings {
+            warmup: Duration::from_millis(100),
+            measure: Duration::from_millis(5000),
+            cooldown: Duration::from_secs(1),
+        }
+    }
+}
+
+pub static TIMINGS: OnceLock<Timings> = OnceLock::new();
+
+fn timings() -> Timings {
+    *TIMINGS.get().unwrap_or(&Timings::default())
+}
+
+// Accepts e.g. "250ms", "5s", "1.5m" or "10us". A bare number is milliseconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration: {:?}", value))?;
+
+    let seconds = match unit.trim() {
+        "ns" => number / 1e9,
+        "us" | "μs" => number / 1e6,
+        "" | "ms" => number / 1e3,
+        "s" => number,
+        "m" => number * 60.0,
+        unit => return Err(format!("invalid duration unit {:?} in {:?}", unit, value)),
+    };
+
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("duration out of range: {:?}", value))
+}
+
+// `--save-baseline` and `--compare`, the baseline to compare against is loaded up front so a typo
+// fails before we spend minutes benchmarking.
+#[derive(Debug, Default)]
+pub struct Baselines {
+    pub save: Option<String>,
+    pub compare: Option<(String, Baseline)>,
+}
+
+pub static BASELINES: OnceLock<Baselines> = OnceLock::new();
+
+fn baselines() -> &'static Baselines {
+    BASELINES.get_or_init(Baselines::default)
+}
+
+// Everything reported in this run, for `report readme`.
+pub static RESULTS: Mutex<Baseline> = Mutex::new(Baseline::new());
+
+// Machine-readable results should be self-describing enough to diff across machines.
+struct HostInfo {
+    hostname: String,
+    os: &'static str,
+    arch: &'static str,
+    cpus: usize,
+    cpu_model: String,
+}
+
+fn host_info() -> &'static HostInfo {
+    static HOST_INFO: OnceLock<HostInfo> = OnceLock::new();
+    HOST_INFO.get_or_init(|| {
+        let mut buf = [0u8; 256];
+        let hostname = unsafe {
+            if libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) == 0 {
+                let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+                String::from_utf8_lossy(&buf[..len]).into_owned()
+            } else {
+                String::from("unknown")
+            }
+        };
+
+        HostInfo {
+            hostname,
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            cpus: thread::available_parallelism().map_or(1, |n| n.get()),
+            cpu_model: cpu_model().unwrap_or_else(|| String::from("unknown")),
+        }
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn cpu_model() -> Option<String> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo
+        .lines()
+        .find(|line| line.starts_with("model name"))
+        .and_then(|line| line.split(':').nth(1))
+        .map(|model| model.trim().to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn cpu_model() -> Option<String> {
+    let output = std::process::Command::new("sysctl")
+        .arg("-n")
+        .arg("machdep.cpu.brand_string")
+        .output()
+        .ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn cpu_model() -> Option<String> {
+    None
+}
+
+// One row of `--output json` or `--output csv`. Kept flat so the same struct serializes to both.
+// All times are in nanoseconds, the throughput columns are empty for benchmarks without a size.
+#[derive(Serialize)]
+struct Record<'a> {
+    name: &'a str,
+    bytes_per_iteration: usize,
+    iterations: usize,
+    duration_ns: u64,
+    ns_per_iteration: f64,
+    cycles_per_iteration: Option<f64>,
+    cycles_source: &'a str,
+    tsc_hz: Option<f64>,
+    instructions_per_iteration: Option<f64>,
+    instructions_per_cycle: Option<f64>,
+    cache_misses_per_iteration: Option<f64>,
+    branch_misses_per_iteration: Option<f64>,
+    dtlb_load_misses_per_iteration: Option<f64>,
+    // Whether the percentiles are of single iterations or of batch averages.
+    sampling: &'static str,
+    min_ns: Option<f64>,
+    p50_ns: Option<f64>,
+    p90_ns: Option<f64>,
+    p99_ns: Option<f64>,
+    p999_ns: Option<f64>,
+    max_ns: Option<f64>,
+    bytes_per_second: Option<f64>,
+    ns_per_mib: Option<f64>,
+    ns_per_gib: Option<f64>,
+    ns_per_tib: Option<f64>,
+    hostname: &'a str,
+    os: &'a str,
+    arch: &'a str,
+    cpus: usize,
+    cpu_model: &'a str,
+    baseline_ns_per_iteration: Option<f64>,
+    baseline_change: Option<f64>,
+    baseline_verdict: Option<&'static str>,
+}
+
+// TODO: Probably we should just expose duration and iterations, and correct for duration_ratio
+// directly in whatever produces this data structure to simplify consumption.
+pub struct BenchmarkResult {
+    pub iterations: usize,
+    pub duration: Duration,
+    // duration_ratio: f64,
+    // intended_duration: Duration,
+    pub cycles: u64,
+    pub cycles_source: CyclesSource,
+    pub counts: Counts,
+    pub latencies: Histogram,
+    // What the samples in `latencies` are of, single iterations or batch averages.
+    pub sampling: Sampling,
+}
+
+// Where `BenchmarkResult::cycles` came from. Perf counts actual core cycles, the TSC ticks at a
+// fixed reference frequency and only matches the core clock with turbo and frequency scaling off.
+#[derive(Clone, Copy, PartialEq)]
+pub enum CyclesSource {
+    Perf,
+    Tsc,
+    Unavailable,
+}
+
+impl CyclesSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CyclesSource::Perf => "perf",
+            CyclesSource::Tsc => "rdtscp",
+            CyclesSource::Unavailable => "",
+        }
+    }
+}
+
+// How `benchmark` turns wall-clock time into latency samples.
+#[derive(Clone, Copy)]
+pub enum Sampling {
+    // Time batches of iterations and record the mean of each batch. Reading the clock costs
+    // ~20ns, so this is the only option for benchmarks where a single iteration is a few ns.
+    Batched,
+    // Time every iteration on its own. Use this for anything that goes to disk, the network or
+    // another process, where the tail is the interesting part (fsync, tcp, redis, ...).
+    PerIteration,
+}
+
+impl Sampling {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Sampling::Batched => "batched",
+            Sampling::PerIteration => "per_iteration",
+        }
+    }
+}
+
+// Roughly how many samples we aim to collect per warmup's worth of iterations in batched mode.
+const BATCHES_PER_WARMUP: usize = 100;
+
+// Most samples a `Histogram` keeps, 8 MB of them. Batched runs stay far below it, but a
+// per-iteration benchmark at a microsecond an iteration would hit it in one second of measuring.
+const MAX_SAMPLES: usize = 1 << 20;
+
+// Latency samples in nanoseconds, per iteration or per batch depending on `Sampling`. We keep the
+// samples themselves rather than buckets, up to `MAX_SAMPLES`. Past that every sample replaces a
+// kept one with the probability of being kept in a uniform sample of all of them (reservoir
+// sampling), so the percentiles don't favour the start of the run.
+#[derive(Default)]
+pub struct Histogram {
+    pub samples: Vec<f64>,
+    recorded: usize,
+    rng: Option<SmallRng>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Percentiles {
+    pub min: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub p999: f64,
+    pub max: f64,
+}
+
+impl Histogram {
+    pub fn record(&mut self, nanoseconds: f64) {
+        self.recorded += 1;
+        if self.samples.len() < MAX_SAMPLES {
+            self.samples.push(nanoseconds);
+            return;
+        }
+
+        let rng = self.rng.get_or_insert_with(SmallRng::from_entropy);
+        let index = rng.gen_range(0, self.recorded);
+        if index < MAX_SAMPLES {
+            self.samples[index] = nanoseconds;
+        }
+    }
+
+    // Every sample recorded, including the ones the reservoir let go of.
+    pub fn recorded(&self) -> usize {
+        self.recorded
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.samples.iter().sum::<f64>() / self.samples.len().max(1) as f64
+    }
+
+    pub fn stddev(&self) -> f64 {
+        if self.samples.len() < 2 {
+            return 0.0;
+        }
+
+        let mean = self.mean();
+        let variance = self
+            .samples
+            .iter()
+            .map(|sample| (sample - mean).powi(2))
+            .sum::<f64>()
+            / (self.samples.len() - 1) as f64;
+        variance.sqrt()
+    }
+
+    pub fn percentiles(&self) -> Option<Percentiles> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+
+        // Nearest-rank, so every reported value is a latency we actually observed.
+        let rank = |p: f64| {
+            let index = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+            sorted[index.clamp(1, sorted.len()) - 1]
+        };
+
+        Some(Percentiles {
+            min: sorted[0],
+            p50: rank(50.0),
+            p90: rank(90.0),
+            p99: rank(99.0),
+            p999: rank(99.9),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+impl BenchmarkResult {
+    pub fn print_results(&self, name: &str, size_of_type: usize) {
+        let baselines = baselines();
+        let entry = self.baseline_entry(size_of_type);
+        let comparison = baselines.compare.as_ref().and_then(|(_, baseline)| {
+            baseline
+                .get(name)
+                .map(|previous| baseline::compare(previous, &entry))
+        });
+
+        match output_format() {
+            OutputFormat::Text => {
+                self.print_text(name, size_of_type);
+                if let Some((baseline_name, _)) = &baselines.compare {
+                    self.print_comparison(name, baseline_name, comparison.as_ref());
+                }
+            }
+            OutputFormat::Json => {
+                println!(
+                    "{}",
+                    serde_json::to_string(&self.record(name, size_of_type, comparison.as_ref()))
+                        .unwrap()
+                );
+            }
+            OutputFormat::Csv => {
+                // Every benchmark prints its own row, so only the first one gets the header.
+                static HEADER_WRITTEN: AtomicBool = AtomicBool::new(false);
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(!HEADER_WRITTEN.swap(true, Ordering::Relaxed))
+                    .from_writer(io::stdout());
+                writer
+                    .serialize(self.record(name, size_of_type, comparison.as_ref()))
+                    .unwrap();
+                writer.flush().unwrap();
+            }
+        }
+
+        RESULTS
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), entry.clone());
+
+        if let Some(baseline_name) = &baselines.save {
+            baseline::save(baseline_name, name, entry).unwrap_or_else(|err| {
+                eprintln!(
+                    "failed to save baseline {}: {}",
+                    baseline::path(baseline_name).display(),
+                    err
+                )
+            });
+        }
+    }
+
+    pub fn baseline_entry(&self, size_of_type: usize) -> baseline::Entry {
+        baseline::Entry {
+            ns_per_iteration: self.duration.as_nanos() as f64 / self.iterations as f64,
+            bytes_per_iteration: size_of_type,
+            sample_mean_ns: self.latencies.mean(),
+            sample_stddev_ns: self.latencies.stddev(),
+            samples: self.latencies.samples.len(),
+        }
+    }
+
+    fn print_comparison(&self, name: &str, baseline_name: &str, comparison: Option<&Comparison>) {
+        match comparison {
+            Some(comparison) => println!(
+                "[{}] Change vs baseline '{}': {:+.2}% ({} -> {}), {}",
+                name,
+                baseline_name,
+                comparison.change * 100.0,
+                self.get_appropriate_nanos_unit(comparison.baseline_ns_per_iteration),
+                self.get_appropriate_nanos_unit(
+                    self.duration.as_nanos() as f64 / self.iterations as f64
+                ),
+                comparison.verdict.as_str(),
+            ),
+            None => println!(
+                "[{}] Change vs baseline '{}': not in baseline",
+                name, baseline_name
+            ),
+        }
+    }
+
+    fn record<'a>(
+        &self,
+        name: &'a str,
+        size_of_type: usize,
+        comparison: Option<&Comparison>,
+    ) -> Record<'a> {
+        let host = host_info();
+        let percentiles = self.latencies.percentiles();
+        let ns_per_iteration = self.duration.as_nanos() as f64 / self.iterations as f64;
+        let ns_per_byte = if size_of_type > 0 {
+            Some(ns_per_iteration / size_of_type as f64)
+        } else {
+            None
+        };
+
+        Record {
+            name,
+            bytes_per_iteration: size_of_type,
+            iterations: self.iterations,
+            duration_ns: self.duration.as_nanos() as u64,
+            ns_per_iteration,
+            cycles_per_iteration: self.per_iteration(self.cycles()),
+            cycles_source: self.cycles_source.as_str(),
+            tsc_hz: counters::tsc_hz(),
+            instructions_per_iteration: self.per_iteration(self.counts.instructions),
+            instructions_per_cycle: self.instructions_per_cycle(),
+            cache_misses_per_iteration: self.per_iteration(self.counts.cache_misses),
+            branch_misses_per_iteration: self.per_iteration(self.counts.branch_misses),
+            dtlb_load_misses_per_iteration: self.per_iteration(self.counts.dtlb_load_misses),
+            sampling: self.sampling.as_str(),
+            min_ns: percentiles.map(|p| p.min),
+            p50_ns: percentiles.map(|p| p.p50),
+            p90_ns: percentiles.map(|p| p.p90),
+            p99_ns: percentiles.map(|p| p.p99),
+            p999_ns: percentiles.map(|p| p.p999),
+            max_ns: percentiles.map(|p| p.max),
+            bytes_per_second: ns_per_byte.map(|ns| 1e9 / ns),
+            ns_per_mib: ns_per_byte.map(|ns| ns * n_mib_bytes!(1) as f64),
+            ns_per_gib: ns_per_byte.map(|ns| ns * n_gib_bytes!(1) as f64),
+            ns_per_tib: ns_per_byte.map(|ns| ns * n_tib_bytes!(1) as f64),
+            hostname: &host.hostname,
+            os: host.os,
+            arch: host.arch,
+            cpus: host.cpus,
+            cpu_model: &host.cpu_model,
+            baseline_ns_per_iteration: comparison.map(|c| c.baseline_ns_per_iteration),
+            baseline_change: comparison.map(|c| c.change),
+            baseline_verdict: comparison.map(|c| c.verdict.as_str()),
+        }
+    }
+
+    pub fn ns_per_iteration(&self) -> f64 {
+        self.duration.as_nanos() as f64 / self.iterations as f64
+    }
+
+    pub fn cycles(&self) -> Option<u64> {
+        match self.cycles_source {
+            CyclesSource::Unavailable => None,
+            _ => Some(self.cycles),
+        }
+    }
+
+    pub fn per_iteration(&self, count: Option<u64>) -> Option<f64> {
+        count.map(|count| count as f64 / self.iterations as f64)
+    }
+
+    // Only meaningful against real core cycles, not TSC ticks.
+    pub fn instructions_per_cycle(&self) -> Option<f64> {
+        match (self.counts.instructions, self.counts.cycles) {
+            (Some(instructions), Some(cycles)) if cycles > 0 => {
+                Some(instructions as f64 / cycles as f64)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn print_text(&self, name: &str, size_of_type: usize) {
+        let mut name = String::from(name);
+        if size_of_type > 0 {
+            write!(
+                name,
+                " <{}>",
+                Byte::from_bytes(size_of_type as u128)
+                    .get_appropriate_unit(true)
+                    .format(0)
+            )
+            .unwrap();
+        }
+
+        println!(
+            "\n[{}] Iterations in {} miliseconds, no overhead: {}",
+            name,
+            self.duration.as_millis(),
+            self.iterations.to_formatted_string(&Locale::en)
+        );
+
+        println!(
+            "[{}] Iterations / second: {}",
+            name,
+            (((self.iterations as f64 / self.duration.as_millis() as f64) * 1000.0) as u128)
+                .to_formatted_string(&Locale::en)
+        );
+
+        if size_of_type > 0 {
+            println!(
+                "[{}] Bytes handled per iteration: {} bytes",
+                name, size_of_type
+            );
+
+            let total_bytes_pushed = size_of_type * self.iterations;
+            println!(
+                "[{}] Total bytes processed: {}",
+                name,
+                Byte::from_bytes(total_bytes_pushed as u128)
+                    .get_appropriate_unit(true)
+                    .format(3)
+            );
+
+            let bytes_per_second =
+                ((total_bytes_pushed as f64) / self.duration.as_millis() as f64) * 1000.0;
+            println!(
+                "[{}] Throughput: {}/s",
+                name,
+                // TODO: Too hard to get right when values aren't just printed!
+                Byte::from_bytes(bytes_per_second as u128)
+                    .get_appropriate_unit(true)
+                    .format(3)
+            );
+        }
+
+        let time_unit = self
+            .get_appropriate_nanos_unit(self.duration.as_nanos() as f64 / self.iterations as f64);
+
+        println!("[{}] Avg single iteration: {}", name, time_unit);
+
+        // Batched samples are the mean of a batch, which hides the tail of single iterations.
+        let (sample, unit) = match self.sampling {
+            Sampling::PerIteration => ("Single iteration", "samples"),
+            Sampling::Batched => ("Batch average", "batches"),
+        };
+        if let Some(percentiles) = self.latencies.percentiles() {
+            println!(
+                "[{}] {} min: {}, p50: {}, p90: {}, p99: {}, p999: {}, max: {} ({} {})",
+                name,
+                sample,
+                self.get_appropriate_nanos_unit(percentiles.min),
+                self.get_appropriate_nanos_unit(percentiles.p50),
+                self.get_appropriate_nanos_unit(percentiles.p90),
+                self.get_appropriate_nanos_unit(percentiles.p99),
+                self.get_appropriate_nanos_unit(percentiles.p999),
+                self.get_appropriate_nanos_unit(percentiles.max),
+                self.latencies.recorded().to_formatted_string(&Locale::en),
+                unit,
+            );
+        }
+
+        if let Some(single_operation_cycles) = self.per_iteration(self.cycles()) {
+            let source = match (self.cycles_source, counters::tsc_hz()) {
+                (CyclesSource::Tsc, Some(hz)) => format!("rdtscp, {:.2} GHz TSC", hz / 1e9),
+                (source, _) => String::from(source.as_str()),
+            };
+            println!(
+                "[{}] Avg single iteration cycles: {:.2} ({})",
+                name, single_operation_cycles, source,
+            );
+        }
+
+        if let Some(instructions) = self.per_iteration(self.counts.instructions) {
+            match self.instructions_per_cycle() {
+                Some(ipc) => println!(
+                    "[{}] Avg single iteration instructions: {:.2}, IPC: {:.2}",
+                    name, instructions, ipc
+                ),
+                None => println!(
+                    "[{}] Avg single iteration instructions: {:.2}",
+                    name, instructions
+                ),
+            }
+        }
+
+        if let Some(cache_misses) = self.per_iteration(self.counts.cache_misses) {
+            println!(
+                "[{}] Avg single iteration cache misses: {:.3}",
+                name, cache_misses
+            );
+        }
+
+        if let Some(branch_misses) = self.per_iteration(self.counts.branch_misses) {
+            println!(
+                "[{}] Avg single iteration branch misses: {:.3}",
+                name, branch_misses
+            );
+        }
+
+        if let Some(dtlb_load_misses) = self.per_iteration(self.counts.dtlb_load_misses) {
+            println!(
+                "[{}] Avg single iteration dTLB load misses: {:.3}",
+                name, dtlb_load_misses
+            );
+        }
+
+        if size_of_type > 0 {
+            let single_op_nanos = self.duration.as_nanos() as f64 / self.iterations as f64;
+            let nanoseconds_per_byte = 1.0 / ((size_of_type as f64) / single_op_nanos);
+            let nanoseconds_per_mebibyte = nanoseconds_per_byte * n_mib_bytes!(1) as f64;
+            let duration_per_mebibyte = Duration::from_nanos(nanoseconds_per_mebibyte as u64);
+
+            println!(
+                "[{}] Time to process 1 MiB: {}",
+                name,
+                self.get_appropriate_time_unit(duration_per_mebibyte),
+            );
+
+            let nanoseconds_per_gibibyte = nanoseconds_per_byte * n_gib_bytes!(1) as f64;
+            let duration_per_gibibyte = Duration::from_nanos(nanoseconds_per_gibibyte as u64);
+
+            println!(
+                "[{}] Time to process 1 GiB: {}",
+                name,
+                self.get_appropriate_time_unit(duration_per_gibibyte),
+            );
+
+            let nanoseconds_per_tibibyte = nanoseconds_per_byte * n_tib_bytes!(1) as f64;
+            let duration_per_tibibyte = Duration::from_nanos(nanoseconds_per_tibibyte as u64);
+
+            println!(
+                "[{}] Time to process 1 TiB: {}",
+                name,
+                self.get_appropriate_time_unit(duration_per_tibibyte),
+            );
+        }
+    }
+
+    // Like `get_appropriate_time_unit`, but keeps sub-10ns values precise since that's where most
+    // of the memory benchmarks land.
+    pub fn get_appropriate_nanos_unit(&self, nanoseconds: f64) -> String {
+        if nanoseconds <= 10.0 {
+            format!("{:.3} ns", nanoseconds)
+        } else {
+            self.get_appropriate_time_unit(Duration::from_nanos(nanoseconds as u64))
+        }
+    }
+
+    // impl on duration
+    pub fn get_appropriate_time_unit(&self, duration: Duration) -> String {
+        if duration.as_nanos() < 1000 {
+            format!("{} ns", duration.as_nanos())
+        } else if duration.as_nanos() > 1000 && duration.as_millis() < 5 {
+            format!("{} μs", duration.as_micros())
+        } else if duration.as_micros() > 1000 && duration.as_millis() < 3000 {
+            format!("{} ms", duration.as_millis())
+        } else if duration.as_secs() <= 120 {
+            format!("{:.2} s", duration.as_millis() as f64 / 1000.0)
+        } else if duration.as_secs() <= 3600 {
+            format!("{:.2} min", (duration.as_secs() as f64) / 60.0)
+        } else {
+            format!("{:.2} hours", (duration.as_secs() as f64) / 3600.0)
+        }
+    }
+}
+
+// What an iteration tells `benchmark` to do next.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Control {
+    Continue,
+    // The iteration used up its state, e.g. a sort that's now sorted or a file that's been read to
+    // the end. Run `setup` again, outside of the measurement, and carry on.
+    ResetWithSetup,
+    // The iteration can't meaningfully be repeated, measure what we have.
+    Stop,
+}
+
+pub fn benchmark<T, F: Fn() -> io::Result<T>, V: FnMut(&mut T) -> Control>(
+    sampling: Sampling,
+    setup: F,
+    f: V,
+) -> io::Result<BenchmarkResult> {
+    measure(sampling, true, setup, f)
+}
+
+// For work that runs on threads `setup` started. The perf counters follow the calling thread, and
+// with inherit only the threads it starts after they're opened, so they'd count an idle thread
+// waiting on the others. Cycles come from the TSC instead and the other events are left out.
+pub fn benchmark_threaded<T, F: Fn() -> io::Result<T>, V: FnMut(&mut T) -> Control>(
+    sampling: Sampling,
+    setup: F,
+    f: V,
+) -> io::Result<BenchmarkResult> {
+    measure(sampling, false, setup, f)
+}
+
+fn measure<T, F: Fn() -> io::Result<T>, V: FnMut(&mut T) -> Control>(
+    sampling: Sampling,
+    count_events: bool,
+    setup: F,
+    mut f: V,
+) -> io::Result<BenchmarkResult> {
+    let timings = timings();
+
+    // warmup run
+    let mut val = setup()?;
+    let intended_duration = timings.warmup;
+    let mut iterations: usize = 0;
+    // Time spent in `setup` after a `ResetWithSetup`, which doesn't count towards either window.
+    let mut excluded = Duration::ZERO;
+    let instant = Instant::now();
+
+    while instant.elapsed() - excluded < intended_duration {
+        iterations += 1;
+        match f(&mut val) {
+            Control::Continue => {}
+            Control::ResetWithSetup => {
+                let setup_instant = Instant::now();
+                drop(val);
+                val = setup()?;
+                excluded += setup_instant.elapsed();
+            }
+            Control::Stop => break,
+        }
+    }
+
+    drop(val);
+    thread::sleep(timings.cooldown);
+    // real run
+    let mut val = setup()?;
+    let intended_duration = timings.measure;
+    let iterations_per_check = match sampling {
+        Sampling::Batched => (iterations / BATCHES_PER_WARMUP).max(1),
+        Sampling::PerIteration => 1,
+    };
+    let mut iterations: usize = 0;
+    let mut latencies = Histogram::default();
+    let mut excluded = Duration::ZERO;
+    let mut excluded_cycles = 0;
+
+    // Opening the counters is a handful of syscalls, keep it out of the measured window.
+    let counters = if count_events { Counters::open() } else { None };
+    if let Some(counters) = &counters {
+        counters.start();
+    }
+    let tsc_before = counters::rdtscp();
+    let instant = Instant::now();
+
+    // At least one iteration, or there's nothing to divide the duration by.
+    while iterations == 0 || instant.elapsed() - excluded < intended_duration {
+        let batch = Instant::now();
+        let mut batch_iterations = 0;
+        let mut control = Control::Continue;
+        // A batch ends early when the iteration wants something other than `Continue`, so a
+        // sample never straddles a `setup`.
+        while batch_iterations < iterations_per_check && control == Control::Continue {
+            batch_iterations += 1;
+            control = f(&mut val);
+        }
+        latencies.record(batch.elapsed().as_nanos() as f64 / batch_iterations as f64);
+        iterations += batch_iterations;
+
+        match control {
+            Control::Continue => {}
+            Control::ResetWithSetup => {
+                if let Some(counters) = &counters {
+                    counters.pause();
+                }
+                let setup_tsc = counters::rdtscp();
+                let setup_instant = Instant::now();
+                drop(val);
+                val = setup()?;
+                excluded += setup_instant.elapsed();
+                if let (Some(before), Some(after)) = (setup_tsc, counters::rdtscp()) {
+                    excluded_cycles += after - before;
+                }
+                if let Some(counters) = &counters {
+                    counters.resume();
+                }
+            }
+            Control::Stop => break,
+        }
+    }
+    let actual_duration = instant.elapsed() - excluded;
+    let tsc_after = counters::rdtscp();
+    let counts = counters.map_or_else(Counts::default, |counters| counters.stop());
+
+    let (cycles, cycles_source) = match (counts.cycles, tsc_before, tsc_after) {
+        (Some(cycles), _, _) => (cycles, CyclesSource::Perf),
+        (None, Some(before), Some(after)) => (after - before - excluded_cycles, CyclesSource::Tsc),
+        _ => (0, CyclesSource::Unavailable),
+    };
+
+    Ok(BenchmarkResult {
+        iterations,
+        duration: actual_duration,
+        // duration_ratio: intended_duration.as_nanos() as f64 / actual_duration.as_nanos() as f64,
+        // intended_duration: intended_duration,
+        cycles,
+        cycles_source,
+        counts,
+        latencies,
+        sampling,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_durations() {
+        let cases = [
+            ("0", Ok(Duration::ZERO)),
+            ("1.5s", Ok(Duration::from_millis(1500))),
+            ("100ms", Ok(Duration::from_millis(100))),
+            // No unit is milliseconds.
+            ("250", Ok(Duration::from_millis(250))),
+            (" 2 m ", Ok(Duration::from_secs(120))),
+            ("10us", Ok(Duration::from_micros(10))),
+        ];
+        for (value, duration) in cases.iter() {
+            assert_eq!(parse_duration(value), *duration, "{:?}", value);
+        }
+
+        for value in [
+            "garbage",
+            "",
+            "s",
+            "5x",
+            "1.2.3s",
+            "99999999999999999999999s",
+        ]
+        .iter()
+        {
+            assert!(parse_duration(value).is_err(), "{:?}", value);
+        }
+    }
+
+    fn histogram(samples: &[f64]) -> Histogram {
+        let mut histogram = Histogram::default();
+        for sample in samples {
+            histogram.record(*sample);
+        }
+        histogram
+    }
+
+    #[test]
+    fn percentiles_of_few_samples() {
+        assert!(histogram(&[]).percentiles().is_none());
+
+        // (samples, [min, p50, p90, p99, p999, max])
+        let cases = [
+            (vec![7.0], [7.0, 7.0, 7.0, 7.0, 7.0, 7.0]),
+            (vec![20.0, 10.0], [10.0, 10.0, 20.0, 20.0, 20.0, 20.0]),
+        ];
+        for (samples, expected) in cases.iter() {
+            let histogram = histogram(samples);
+            let p = histogram.percentiles().unwrap();
+            assert_eq!(
+                [p.min, p.p50, p.p90, p.p99, p.p999, p.max],
+                *expected,
+                "{:?}",
+                samples
+            );
+            assert_eq!(histogram.recorded(), samples.len());
+        }
+    }
+}
@@ -0,0 +1,150 @@
+// NUMA nodes as the kernel reports them, and putting memory on a given one, so the NUMA benchmarks
+// can compare memory next to the reading core against memory on the other socket.
+
+use std::io;
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub id: usize,
+    // Empty for nodes that only have memory, e.g. CXL expanders.
+    pub cpus: Vec<usize>,
+    // The kernel's relative cost of reaching each node's memory from this one, in node order. 10 is
+    // local.
+    pub distances: Vec<u32>,
+}
+
+// Smallest id first. Empty when the kernel wasn't built with NUMA or isn't Linux, which for our
+// purposes is the same as a single node.
+#[cfg(target_os = "linux")]
+pub fn nodes() -> Vec<Node> {
+    use std::fs;
+
+    let mut nodes = Vec::new();
+    let entries = match fs::read_dir("/sys/devices/system/node") {
+        Ok(entries) => entries,
+        Err(_) => return nodes,
+    };
+
+    for entry in entries.flatten() {
+        let id = match entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_prefix("node"))
+            .and_then(|id| id.parse().ok())
+        {
+            Some(id) => id,
+            None => continue,
+        };
+        let read = |file: &str| fs::read_to_string(entry.path().join(file)).unwrap_or_default();
+        let cpus = parse_list(read("cpulist").trim()).unwrap_or_default();
+        let distances = read("distance")
+            .split_whitespace()
+            .filter_map(|distance| distance.parse().ok())
+            .collect();
+
+        nodes.push(Node {
+            id,
+            cpus,
+            distances,
+        });
+    }
+
+    nodes.sort_by_key(|node| node.id);
+    nodes
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn nodes() -> Vec<Node> {
+    Vec::new()
+}
+
+// The node to read from and the node whose memory is remote to it: the first node with cores, and
+// the first other node. None on a single node.
+pub fn local_and_remote() -> Option<(Node, Node)> {
+    let nodes = nodes();
+    let local = nodes.iter().find(|node| !node.cpus.is_empty())?;
+    let remote = nodes.iter().find(|node| node.id != local.id)?;
+    Some((local.clone(), remote.clone()))
+}
+
+// sysfs writes cpu lists as "0-3,8-11", memory-only nodes as "".
+#[cfg(target_os = "linux")]
+fn parse_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for range in list.split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((first, last)) => cpus.extend(first.parse::<usize>().ok()?..=last.parse().ok()?),
+            None => cpus.push(range.parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+// Not in the libc crate yet, from <linux/mempolicy.h>.
+#[cfg(target_os = "linux")]
+const MPOL_MF_STRICT: libc::c_ulong = 1 << 0;
+#[cfg(target_os = "linux")]
+const MPOL_MF_MOVE: libc::c_ulong = 1 << 1;
+
+// Binds the pages behind `memory` to `node` with mbind(2), moving the ones already touched, the
+// same as `numactl --membind` for one allocation. Only whole pages can be bound, so the partial
+// pages at either end stay where they are.
+#[cfg(target_os = "linux")]
+pub fn bind<T>(memory: &mut [T], node: usize) -> io::Result<()> {
+    let page = page_size::get();
+    let start = (memory.as_mut_ptr() as usize).div_ceil(page) * page;
+    let end = (memory.as_mut_ptr() as usize + std::mem::size_of_val(memory)) / page * page;
+    if end <= start {
+        return Ok(());
+    }
+
+    let mut mask = vec![0 as libc::c_ulong; node / 64 + 1];
+    mask[node / 64] |= 1 << (node % 64);
+    // The kernel reads one bit less than `maxnode`.
+    let maxnode = mask.len() * 64 + 1;
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            start,
+            end - start,
+            libc::MPOL_BIND,
+            mask.as_ptr(),
+            maxnode,
+            MPOL_MF_MOVE | MPOL_MF_STRICT,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn bind<T>(_memory: &mut [T], _node: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "binding memory to a NUMA node needs Linux",
+    ))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cpu_lists() {
+        let cases = [
+            ("", Some(vec![])),
+            ("0", Some(vec![0])),
+            ("0-3", Some(vec![0, 1, 2, 3])),
+            ("0-1,8-9", Some(vec![0, 1, 8, 9])),
+            ("2,4", Some(vec![2, 4])),
+            ("0-", None),
+            ("a", None),
+        ];
+        for (list, cpus) in cases.iter() {
+            assert_eq!(parse_list(list), *cpus, "{:?}", list);
+        }
+    }
+}
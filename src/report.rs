@@ -0,0 +1,1475 @@
+// Every `report` format is a row of `REPORTS`: the tests it runs and how it renders their results.
+// `report readme` renders the "Numbers" table in the README from measured results, rounded the
+// same way the hand-maintained table is: one significant digit, in the unit that keeps it >= 1.
+// `list` renders what's in the registry.
+
+use crate::baseline::{Baseline, Entry};
+use crate::benchmarks::{
+    alloc_threaded_title, alloc_title, atomic_title, bandwidth_title, copy_sweep,
+    copy_threaded_title, copy_title, core_distance, dependent_read_sweep_title, fault_title,
+    global_allocator, layout_title, numa_title, page_read_title, page_size, ping_pong_title,
+    random_read_sweep, random_read_sweep_title, stride_sweep, stride_title, switch_title,
+    syscall_title, DynBenchmark, Fault, PageRead, Pages, Peer, Placement, Registry, SwitchCores,
+    SyscallPath, Workload, ALLOCATORS, ALLOC_OPS, ATOMIC_OPS, COPY_KERNELS, CREATIONS, LAYOUTS,
+    LAYOUT_ELEMENTS, MECHANISMS, NUMA_WORKLOADS, PAGES, PING_PONG_ROUND_TRIPS, SHARINGS, SYSCALLS,
+    THREADED_ALLOCATIONS, WORKLOADS,
+};
+use crate::cache::{self, CacheLevel};
+use crate::numa;
+use byte_unit::Byte;
+use std::fmt::Write;
+use std::time::Duration;
+
+pub struct Report {
+    // What `report` takes, e.g. `caches`.
+    pub format: &'static str,
+    // The tests it runs without -e. `None` for `readme`, which runs the ones with a README row.
+    regex: Option<&'static str>,
+    pub render: fn(&Baseline) -> String,
+    // What it renders, for `report --help`.
+    description: &'static str,
+}
+
+const fn report(
+    format: &'static str,
+    regex: &'static str,
+    render: fn(&Baseline) -> String,
+    description: &'static str,
+) -> Report {
+    Report {
+        format,
+        regex: Some(regex),
+        render,
+        description,
+    }
+}
+
+pub const REPORTS: [Report; 14] = [
+    Report {
+        format: "readme",
+        regex: None,
+        render: readme,
+        description: "the README's rows of the Numbers table",
+    },
+    report(
+        "caches",
+        "^memory_read_(random|dependent)_[0-9]+[kmg]ib$",
+        caches,
+        "the cache levels from the random read sweep",
+    ),
+    report(
+        "scaling",
+        "^memory_bandwidth_",
+        scaling,
+        "bandwidth against threads",
+    ),
+    report("numa", "^memory_numa_", numa, "the NUMA penalty"),
+    report(
+        "pages",
+        "^memory_read_(random|dependent)_pages_",
+        pages,
+        "reads per page size",
+    ),
+    report(
+        "strides",
+        "^memory_read_stride_",
+        strides,
+        "the stride sweep",
+    ),
+    report("copy", "^memory_copy_", copy, "copy bandwidth"),
+    report(
+        "contention",
+        "^(atomic|core_ping_pong)_",
+        contention,
+        "core-to-core contention",
+    ),
+    report("layout", "^layout_scan_", layout, "struct layouts"),
+    report("switches", "^context_switch_", switches, "context switches"),
+    report("syscalls", "^syscall_(libc|raw)_", syscalls, "syscalls"),
+    report(
+        "spawn",
+        "^(thread_spawn|thread_pool_dispatch|process_)",
+        spawn,
+        "thread and process creation",
+    ),
+    report(
+        "faults",
+        "^(page_fault_|mmap_munmap_)",
+        faults,
+        "page faults",
+    ),
+    report("allocators", "^alloc_", allocators, "allocators"),
+];
+
+impl Report {
+    pub fn find(format: &str) -> Option<&'static Report> {
+        REPORTS.iter().find(|report| report.format == format)
+    }
+
+    // What -e defaults to.
+    pub fn regex(&self) -> String {
+        match self.regex {
+            Some(regex) => String::from(regex),
+            None => format!(
+                "^({})$",
+                README_ROWS
+                    .iter()
+                    .map(|row| row.method)
+                    .collect::<Vec<_>>()
+                    .join("|")
+            ),
+        }
+    }
+}
+
+// The `about` of the `report` subcommand, one clause per format.
+pub fn about() -> String {
+    let descriptions: Vec<&str> = REPORTS.iter().map(|report| report.description).collect();
+    let (last, rest) = descriptions.split_last().unwrap();
+    format!(
+        "Runs the tests behind a report and renders {}, or {}",
+        rest.join(", "),
+        last
+    )
+}
+
+pub struct ReadmeRow {
+    // First column of the README table, verbatim.
+    pub operation: &'static str,
+    // Name of the benchmark that produces the number...
+    pub method: &'static str,
+    // ...and the name it reports its results under.
+    pub result: &'static str,
+    pub latency: bool,
+    pub throughput: bool,
+    // What the README puts in the columns we don't measure for this row: "", "N/A" or "?".
+    pub filler: &'static str,
+    // How many of the operation one iteration does, e.g. 2 context switches per round trip.
+    pub per_iteration: usize,
+}
+
+const fn row(
+    operation: &'static str,
+    method: &'static str,
+    result: &'static str,
+    latency: bool,
+    throughput: bool,
+    filler: &'static str,
+) -> ReadmeRow {
+    ReadmeRow {
+        operation,
+        method,
+        result,
+        latency,
+        throughput,
+        filler,
+        per_iteration: 1,
+    }
+}
+
+// In README order. Rows we can't measure from a single host (network, blob storage, ..) are left
+// for humans.
+pub const README_ROWS: [ReadmeRow; 15] = [
+    row(
+        "Sequential Memory R/W (64 bytes)",
+        "memory_read_sequential",
+        "Read Seq Vec",
+        true,
+        false,
+        "",
+    ),
+    row(
+        "├ Single Thread",
+        "memory_read_sequential",
+        "Read Seq Vec",
+        false,
+        true,
+        "",
+    ),
+    row(
+        "├ Threaded",
+        "memory_read_sequential_threaded",
+        "Read Seq Vec Threaded",
+        false,
+        true,
+        "",
+    ),
+    row(
+        "Hashing, not crypto-safe (64 bytes)",
+        "hash_siphash",
+        "SIPHash",
+        true,
+        true,
+        "",
+    ),
+    row(
+        "Random Memory R/W (64 bytes)",
+        "memory_read_random",
+        "Random Read Vec",
+        true,
+        true,
+        "",
+    ),
+    // A raw syscall, so it's a kernel transition whatever libc would have done for getpid.
+    row(
+        "System Call",
+        "syscall_raw_getpid",
+        "Syscall getpid(2) raw",
+        true,
+        false,
+        "N/A",
+    ),
+    row(
+        "Hashing, crypto-safe (64 bytes)",
+        "hash_sha256",
+        "Sha256",
+        true,
+        true,
+        "",
+    ),
+    row(
+        "Sequential SSD read (8 KiB)",
+        "disk_read_sequential",
+        "Sequential Disk Read",
+        true,
+        true,
+        "",
+    ),
+    // Two processes on one core, like the measurements the README cites.
+    ReadmeRow {
+        per_iteration: 2,
+        ..row(
+            "Context Switch `[1] [2]`",
+            "context_switch_futex_process_same_core",
+            "Context switch, futex to a process on the same core",
+            true,
+            false,
+            "N/A",
+        )
+    },
+    row(
+        "Sequential SSD write, -fsync (8KiB)",
+        "disk_write_sequential_no_fsync",
+        "Sequential Disk Write, No Fsync",
+        true,
+        true,
+        "",
+    ),
+    row(
+        "TCP Echo Server (32 KiB)",
+        "tcp_read_write",
+        "Tcp Echo",
+        true,
+        true,
+        "",
+    ),
+    row(
+        "Random SSD Read (8 KiB)",
+        "disk_read_random",
+        "Random Disk Seek, No Page Cache",
+        true,
+        true,
+        "",
+    ),
+    row(
+        "Sorting (64-bit integers)",
+        "sort",
+        "Sort",
+        false,
+        true,
+        "N/A",
+    ),
+    row(
+        "Sequential SSD write, +fsync (8KiB)",
+        "disk_write_sequential_fsync",
+        "Sequential Disk Write, Fsync",
+        true,
+        true,
+        "",
+    ),
+    row(
+        "{MySQL, Memcached, Redis, ..} Query",
+        "redis_read_single_key",
+        "Redis Read",
+        true,
+        false,
+        "?",
+    ),
+];
+
+// Round to one significant digit, 0.47 => 0.5, 17.88 => 20, 304 => 300.
+fn round_napkin(value: f64) -> f64 {
+    if value <= 0.0 || !value.is_finite() {
+        return value;
+    }
+
+    let magnitude = 10f64.powf(value.log10().floor());
+    (value / magnitude).round() * magnitude
+}
+
+// Prints without trailing zeros and without float noise, so 0.5 and 20 rather than 0.500000001.
+fn format_number(value: f64) -> String {
+    if value >= 1.0 {
+        format!("{}", value.round() as u64)
+    } else {
+        let formatted = format!("{:.3}", value);
+        formatted
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    }
+}
+
+// Matches the README's style: "0.5 ns", "50 μs", "300 ms", "2s", "30m".
+fn format_time(nanoseconds: f64) -> String {
+    let nanoseconds = round_napkin(nanoseconds);
+    let units = [
+        (60e9, "m"),
+        (1e9, "s"),
+        (1e6, " ms"),
+        (1e3, " μs"),
+        (1.0, " ns"),
+    ];
+
+    for (scale, unit) in units.iter() {
+        if nanoseconds >= *scale {
+            // Rounding again after scaling keeps e.g. 90s => 2m rather than 1.5m.
+            return format!(
+                "{}{}",
+                format_number(round_napkin(nanoseconds / scale)),
+                unit
+            );
+        }
+    }
+
+    format!("{} ns", format_number(nanoseconds))
+}
+
+fn format_throughput(bytes_per_second: f64) -> String {
+    const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+    const MIB: f64 = 1024.0 * 1024.0;
+
+    if bytes_per_second >= GIB {
+        format!(
+            "{} GiB/s",
+            format_number(round_napkin(bytes_per_second / GIB))
+        )
+    } else {
+        format!(
+            "{} MiB/s",
+            format_number(round_napkin(bytes_per_second / MIB))
+        )
+    }
+}
+
+fn cells(row: &ReadmeRow, entry: &Entry) -> [String; 4] {
+    let filler = || String::from(row.filler);
+    let latency = if row.latency {
+        format_time(entry.ns_per_iteration / row.per_iteration as f64)
+    } else {
+        filler()
+    };
+
+    if !row.throughput || entry.bytes_per_iteration == 0 {
+        return [latency, filler(), filler(), filler()];
+    }
+
+    let ns_per_byte = entry.ns_per_iteration / entry.bytes_per_iteration as f64;
+    [
+        latency,
+        format_throughput(1e9 / ns_per_byte),
+        format_time(ns_per_byte * 1024.0 * 1024.0),
+        format_time(ns_per_byte * 1024.0 * 1024.0 * 1024.0),
+    ]
+}
+
+// Renders the rows we have results for. Rows without a result are skipped so a partial run
+// (e.g. no Redis around) still produces something pasteable.
+pub fn readme(results: &Baseline) -> String {
+    let mut table = String::from(TABLE_HEADER);
+
+    for row in README_ROWS.iter() {
+        if let Some(entry) = results.get(row.result) {
+            table.push_str(&table_row(row.operation, cells(row, entry)));
+        }
+    }
+
+    table
+}
+
+const TABLE_HEADER: &str = "\
+| Operation                           | Latency     | Throughput | 1 MiB  | 1 GiB  |
+| ----------------------------------- | -------     | ---------- | ------ | ------ |
+";
+
+fn table_row(operation: &str, cells: [String; 4]) -> String {
+    let [latency, throughput, mebibyte, gibibyte] = cells;
+    format!(
+        "| {:<35} | {:<11} | {:<10} | {:<6} | {:<6} |\n",
+        operation, latency, throughput, mebibyte, gibibyte
+    )
+}
+
+fn format_bytes(bytes: usize) -> String {
+    Byte::from_bytes(bytes as u128)
+        .get_appropriate_unit(true)
+        .format(1)
+}
+
+fn footprint(benchmark: &dyn DynBenchmark) -> String {
+    let mut footprint = Vec::new();
+    if benchmark.memory_footprint() > 0 {
+        footprint.push(format!(
+            "{} memory",
+            format_bytes(benchmark.memory_footprint())
+        ));
+    }
+    if benchmark.disk_footprint() > 0 {
+        footprint.push(format!("{} disk", format_bytes(benchmark.disk_footprint())));
+    }
+
+    if footprint.is_empty() {
+        String::from("-")
+    } else {
+        footprint.join(", ")
+    }
+}
+
+fn requirements(benchmark: &dyn DynBenchmark) -> String {
+    if benchmark.requirements().is_empty() {
+        return String::from("-");
+    }
+
+    benchmark
+        .requirements()
+        .iter()
+        .map(|requirement| {
+            if requirement.is_met() {
+                String::from(requirement.as_str())
+            } else {
+                format!("{} (missing)", requirement.as_str())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// `runtime` is warmup + cooldown + measurement. Setup runs once before the warmup and once before
+// measuring, which is where the footprint goes and what dominates for the disk tests.
+pub fn list(registry: &Registry, runtime: Duration) -> String {
+    let mut list = String::new();
+
+    for benchmark in registry.iter() {
+        let mut tags = vec![benchmark.category().as_str()];
+        tags.extend(benchmark.tags());
+        let readme_rows: Vec<&str> = README_ROWS
+            .iter()
+            .filter(|row| row.method == benchmark.name())
+            .map(|row| row.operation)
+            .collect();
+        let readme_rows = if readme_rows.is_empty() {
+            String::from("-")
+        } else {
+            readme_rows.join(", ")
+        };
+
+        writeln!(list, "{} ({})", benchmark.name(), benchmark.title()).unwrap();
+        writeln!(list, "  {}", benchmark.description()).unwrap();
+        writeln!(list, "  tags: {}", tags.join(", ")).unwrap();
+        writeln!(list, "  README: {}", readme_rows).unwrap();
+        // The ones that stop at the end of their working set can be done well before the window.
+        let bound = if benchmark.stops_early() {
+            "up to "
+        } else {
+            ""
+        };
+        writeln!(
+            list,
+            "  runtime: {}~{} + setup",
+            bound,
+            format_time(runtime.as_nanos() as f64)
+        )
+        .unwrap();
+        writeln!(list, "  footprint: {}", footprint(benchmark)).unwrap();
+        writeln!(list, "  needs: {}\n", requirements(benchmark)).unwrap();
+    }
+
+    list
+}
+
+// A working set is on a new plateau once its latency is this much above the fastest size on the
+// current one. Latency creeps up within a level as the TLB and prefetchers run out, it jumps
+// between levels.
+const PLATEAU_JUMP: f64 = 1.4;
+
+// Sizes in the sweep and from the OS are round, so 1 MiB rather than byte_unit's 1024 KiB.
+pub fn format_size(bytes: usize) -> String {
+    let units = [(1 << 30, "GiB"), (1 << 20, "MiB"), (1 << 10, "KiB")];
+    for (scale, unit) in units.iter() {
+        if bytes >= *scale && bytes.is_multiple_of(*scale) {
+            return format!("{} {}", bytes / scale, unit);
+        }
+    }
+    format!("{} B", bytes)
+}
+
+fn median(points: &[(usize, f64)]) -> f64 {
+    let mut latencies: Vec<f64> = points.iter().map(|(_, ns)| *ns).collect();
+    latencies.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+    latencies[latencies.len() / 2]
+}
+
+// Splits the sweep into runs of sizes with roughly the same latency. Sizes on the way from one
+// level to the next end up on their own, so single-size plateaus are dropped.
+fn plateaus(points: &[(usize, f64)]) -> Vec<&[(usize, f64)]> {
+    let mut plateaus = Vec::new();
+    let mut start = 0;
+    let mut fastest = f64::INFINITY;
+
+    for (i, (_, ns)) in points.iter().enumerate() {
+        if *ns > fastest * PLATEAU_JUMP {
+            plateaus.push(&points[start..i]);
+            start = i;
+            fastest = f64::INFINITY;
+        }
+        fastest = fastest.min(*ns);
+    }
+    if start < points.len() {
+        plateaus.push(&points[start..]);
+    }
+
+    plateaus.retain(|plateau| plateau.len() > 1);
+    plateaus
+}
+
+// The cache level a plateau ending at `size` fits in, closest on a log scale, or None past the
+// last level.
+fn nearest_level(levels: &[CacheLevel], size: usize) -> Option<&CacheLevel> {
+    let distance = |level: &&CacheLevel| ((level.size as f64).log2() - (size as f64).log2()).abs();
+    levels
+        .iter()
+        .filter(|level| size <= level.size * 2)
+        .min_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap())
+}
+
+// Renders the random and dependent read latency of every size in the sweep we have results for,
+// the plateaus in it and README rows for each cache level. The rows use the cache sizes the OS
+// reports when it does: sizes up to half a level (so the level above doesn't interfere) and at
+// least 4x past the last level for DRAM. Without them, the detected plateaus are the levels.
+pub fn caches(results: &Baseline) -> String {
+    let series = |title: fn(usize) -> String| -> Vec<(usize, f64)> {
+        random_read_sweep()
+            .filter_map(|size| {
+                results
+                    .get(&title(size))
+                    .map(|entry| (size, entry.ns_per_iteration))
+            })
+            .collect()
+    };
+    let random = series(random_read_sweep_title);
+    let dependent = series(dependent_read_sweep_title);
+    let levels = cache::levels();
+    let mut report = String::new();
+
+    let lookup = |points: &[(usize, f64)], size: usize| {
+        points
+            .iter()
+            .find(|(point, _)| *point == size)
+            .map_or(String::new(), |(_, ns)| format_time(*ns))
+    };
+    writeln!(report, "| Working set | Random read | Dependent read |").unwrap();
+    writeln!(report, "| ----------- | ----------- | -------------- |").unwrap();
+    for size in random_read_sweep() {
+        let (random_ns, dependent_ns) = (lookup(&random, size), lookup(&dependent, size));
+        if random_ns.is_empty() && dependent_ns.is_empty() {
+            continue;
+        }
+        writeln!(
+            report,
+            "| {:<11} | {:<11} | {:<14} |",
+            format_size(size),
+            random_ns,
+            dependent_ns
+        )
+        .unwrap();
+    }
+
+    // Dependent reads are the cleaner signal, independent ones overlap the misses and blur the
+    // edges.
+    let signal = if dependent.is_empty() {
+        &random
+    } else {
+        &dependent
+    };
+    writeln!(report, "\nDetected plateaus:").unwrap();
+    let detected = plateaus(signal);
+    for plateau in detected.iter() {
+        let (first, _) = plateau[0];
+        let (last, _) = plateau[plateau.len() - 1];
+        let level = match nearest_level(&levels, last) {
+            Some(level) => format!("{} is {}", level.name(), format_size(level.size)),
+            None if levels.is_empty() => String::from("no cache sizes from the OS"),
+            None => String::from("past every cache level, DRAM"),
+        };
+        writeln!(
+            report,
+            "  {} - {}: {} ({})",
+            format_size(first),
+            format_size(last),
+            format_time(median(plateau)),
+            level
+        )
+        .unwrap();
+    }
+
+    // Which sizes each row covers, inclusive.
+    let mut rows: Vec<(String, usize, usize)> = Vec::new();
+    if levels.is_empty() {
+        for (i, plateau) in detected.iter().enumerate() {
+            let name = if i == detected.len() - 1 && i > 0 {
+                String::from("├ DRAM")
+            } else {
+                format!("├ L{}", i + 1)
+            };
+            rows.push((name, plateau[0].0, plateau[plateau.len() - 1].0));
+        }
+    } else {
+        let mut previous = 0;
+        for level in levels.iter() {
+            rows.push((
+                format!("├ {} ({})", level.name(), format_size(level.size)),
+                previous + 1,
+                level.size / 2,
+            ));
+            previous = level.size;
+        }
+        rows.push((String::from("├ DRAM"), previous * 4, usize::MAX));
+    }
+
+    // Latency from the dependent reads, throughput from the independent ones.
+    let row = ReadmeRow {
+        operation: "",
+        method: "",
+        result: "",
+        latency: true,
+        throughput: true,
+        filler: "",
+        per_iteration: 1,
+    };
+    writeln!(report, "\n{}", TABLE_HEADER.trim_end()).unwrap();
+    report.push_str(&table_row(
+        "Random Memory Read (64 bytes)",
+        Default::default(),
+    ));
+    for (name, first, last) in rows.iter() {
+        let window = |points: &[(usize, f64)]| -> Option<f64> {
+            let window: Vec<(usize, f64)> = points
+                .iter()
+                .filter(|(size, _)| size >= first && size <= last)
+                .copied()
+                .collect();
+            if window.is_empty() {
+                None
+            } else {
+                Some(median(&window))
+            }
+        };
+
+        let (random_ns, dependent_ns) = (window(&random), window(&dependent));
+        let mut cells = match random_ns.or(dependent_ns) {
+            Some(ns) => cells(
+                &ReadmeRow {
+                    throughput: random_ns.is_some(),
+                    ..row
+                },
+                &Entry {
+                    ns_per_iteration: ns,
+                    bytes_per_iteration: 64,
+                    ..Entry::default()
+                },
+            ),
+            None => continue,
+        };
+        if let Some(ns) = dependent_ns {
+            cells[0] = format_time(ns);
+        }
+        report.push_str(&table_row(name, cells));
+    }
+
+    report
+}
+
+// The thread counts in `results` for any of `keys`, smallest first. The threaded titles all end in
+// "N thread" or "N threads", so the count is read back out of them.
+fn thread_counts<K: Copy>(
+    results: &Baseline,
+    keys: &[K],
+    title: impl Fn(K, usize) -> String,
+) -> Vec<usize> {
+    let mut counts: Vec<usize> = keys
+        .iter()
+        .flat_map(|key| {
+            let single = title(*key, 1);
+            let prefix = single
+                .strip_suffix("1 thread")
+                .unwrap_or(&single)
+                .to_string();
+            results
+                .keys()
+                .filter_map(move |entry| {
+                    entry
+                        .strip_prefix(prefix.as_str())?
+                        .split(' ')
+                        .next()?
+                        .parse()
+                        .ok()
+                })
+                .filter(|threads| results.contains_key(&title(*key, *threads)))
+                .collect::<Vec<usize>>()
+        })
+        .collect();
+    counts.sort_unstable();
+    counts.dedup();
+    counts
+}
+
+// A curve counts as saturated from the first thread count within this much of its best bandwidth.
+const SATURATION: f64 = 0.95;
+
+pub fn scaling(results: &Baseline) -> String {
+    const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+    let mut report = String::new();
+
+    for workload in WORKLOADS.iter() {
+        let curve: Vec<(usize, f64)> = thread_counts(results, &[*workload], bandwidth_title)
+            .into_iter()
+            .filter_map(|threads| {
+                results
+                    .get(&bandwidth_title(*workload, threads))
+                    .map(|entry| {
+                        let seconds = entry.ns_per_iteration / 1e9;
+                        (threads, entry.bytes_per_iteration as f64 / seconds / GIB)
+                    })
+            })
+            .collect();
+        if curve.is_empty() {
+            continue;
+        }
+
+        // The sweep's rounding is too coarse to see a curve flatten, so two decimals here.
+        writeln!(report, "{}:", workload.title()).unwrap();
+        writeln!(report, "| Threads | Bandwidth   | Speedup |").unwrap();
+        writeln!(report, "| ------- | ----------- | ------- |").unwrap();
+        let single = curve[0].1;
+        for (threads, bandwidth) in curve.iter() {
+            writeln!(
+                report,
+                "| {:<7} | {:<11} | {:<7} |",
+                threads,
+                format!("{:.2} GiB/s", bandwidth),
+                format!("{:.2}x", bandwidth / single)
+            )
+            .unwrap();
+        }
+
+        let best = curve
+            .iter()
+            .map(|(_, bandwidth)| *bandwidth)
+            .fold(0.0, f64::max);
+        let (threads, bandwidth) = curve
+            .iter()
+            .find(|(_, bandwidth)| *bandwidth >= best * SATURATION)
+            .unwrap();
+        writeln!(
+            report,
+            "Saturates at {} thread{}, {:.2} GiB/s ({:.0}% of the best {:.2} GiB/s)\n",
+            threads,
+            if *threads == 1 { "" } else { "s" },
+            bandwidth,
+            bandwidth / best * 100.0,
+            best
+        )
+        .unwrap();
+    }
+
+    report
+}
+
+// The topology, then per workload local against remote: the time per read of 64 bytes (what the
+// latency column of the README would say), bandwidth and how much slower remote is.
+pub fn numa(results: &Baseline) -> String {
+    const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+    let mut report = String::new();
+
+    let nodes = numa::nodes();
+    writeln!(report, "NUMA nodes:").unwrap();
+    if nodes.is_empty() {
+        writeln!(report, "  none reported by the OS").unwrap();
+    }
+    for node in nodes.iter() {
+        let distances: Vec<String> = node.distances.iter().map(|d| d.to_string()).collect();
+        writeln!(
+            report,
+            "  node{}: {} cpus, distances {}",
+            node.id,
+            node.cpus.len(),
+            distances.join(" ")
+        )
+        .unwrap();
+    }
+
+    writeln!(
+        report,
+        "\n| Workload        | Local       | Remote      | Penalty |"
+    )
+    .unwrap();
+    writeln!(
+        report,
+        "| --------------- | ----------- | ----------- | ------- |"
+    )
+    .unwrap();
+    for workload in NUMA_WORKLOADS.iter() {
+        let (local, remote) = match (
+            results.get(numa_title(*workload, Placement::Local)),
+            results.get(numa_title(*workload, Placement::Remote)),
+        ) {
+            (Some(local), Some(remote)) => (local, remote),
+            _ => continue,
+        };
+        let cell = |entry: &Entry| {
+            let reads = entry.bytes_per_iteration as f64 / 64.0;
+            let seconds = entry.ns_per_iteration / 1e9;
+            format!(
+                "{} ({:.2} GiB/s)",
+                format_time(entry.ns_per_iteration / reads),
+                entry.bytes_per_iteration as f64 / seconds / GIB
+            )
+        };
+        let workload = match workload {
+            Workload::ReadRandom => "Random read",
+            _ => "Sequential read",
+        };
+        writeln!(
+            report,
+            "| {:<15} | {} | {} | {:<7} |",
+            workload,
+            cell(local),
+            cell(remote),
+            format!("{:.2}x", remote.ns_per_iteration / local.ns_per_iteration)
+        )
+        .unwrap();
+    }
+
+    report
+}
+
+// Per read, the time per access against each page size and the speedup over 4 KiB pages. What's
+// left between the 4 KiB and huge page rows is the time spent walking page tables on TLB misses.
+pub fn pages(results: &Baseline) -> String {
+    let mut report = String::new();
+
+    writeln!(
+        report,
+        "| Read            | Pages                  | Latency | Speedup |"
+    )
+    .unwrap();
+    writeln!(
+        report,
+        "| --------------- | ---------------------- | ------- | ------- |"
+    )
+    .unwrap();
+    for read in [PageRead::Random, PageRead::Dependent].iter() {
+        let small = results.get(page_read_title(*read, Pages::Small));
+        for pages in PAGES.iter() {
+            let entry = match results.get(page_read_title(*read, *pages)) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let speedup = match small {
+                Some(small) => format!("{:.2}x", small.ns_per_iteration / entry.ns_per_iteration),
+                None => "?".to_string(),
+            };
+            writeln!(
+                report,
+                "| {:<15} | {:<22} | {:<7} | {:<7} |",
+                match read {
+                    PageRead::Random => "Random read",
+                    PageRead::Dependent => "Dependent read",
+                },
+                pages.as_str(),
+                format_time(entry.ns_per_iteration),
+                speedup
+            )
+            .unwrap();
+        }
+    }
+
+    report
+}
+
+// A stride counts as unprefetched once a read takes this much of the slowest stride's time.
+const PREFETCH_LIMIT: f64 = 0.8;
+
+// Per stride the time per read, the bandwidth of the bytes asked for and of the cache lines it
+// took to get them. Up to a line the prefetcher streams and the line bandwidth stays flat, after
+// that it drops until reads cost what they cost with no prefetching at all.
+pub fn strides(results: &Baseline) -> String {
+    const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+    let mut report = String::new();
+
+    let points: Vec<(usize, f64)> = stride_sweep()
+        .filter_map(|stride| {
+            results
+                .get(&stride_title(stride))
+                .map(|entry| (stride, entry.ns_per_iteration))
+        })
+        .collect();
+    if points.is_empty() {
+        return report;
+    }
+
+    // Two decimals like `report scaling`, one significant digit can't tell 0.6 from 0.9 ns.
+    writeln!(report, "| Stride | Per read  | Useful      | Cache lines |").unwrap();
+    writeln!(report, "| ------ | --------- | ----------- | ----------- |").unwrap();
+    for (stride, ns) in points.iter() {
+        let line_bytes = (*stride).min(64) as f64;
+        writeln!(
+            report,
+            "| {:<6} | {:<9} | {:<11} | {:<11} |",
+            format_size(*stride),
+            format!("{:.2} ns", ns),
+            format!("{:.2} GiB/s", 8.0 / ns * 1e9 / GIB),
+            format!("{:.2} GiB/s", line_bytes / ns * 1e9 / GIB)
+        )
+        .unwrap();
+    }
+
+    let slowest = points.iter().map(|(_, ns)| *ns).fold(0.0, f64::max);
+    if let Some((stride, ns)) = points
+        .iter()
+        .find(|(stride, ns)| *stride >= 64 && *ns >= slowest * PREFETCH_LIMIT)
+    {
+        writeln!(
+            report,
+            "\nPrefetching stops paying off at a {} stride, {:.2} ns per read ({:.0}% of the slowest {:.2} ns)",
+            format_size(*stride),
+            ns,
+            ns / slowest * 100.0,
+            slowest
+        )
+        .unwrap();
+    }
+
+    report
+}
+
+// Copy bandwidth per size for every kernel, then the 1 GiB copy on one core against every core
+// along with how long it takes, the number most napkin estimates want.
+pub fn copy(results: &Baseline) -> String {
+    const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+    let mut report = String::new();
+    let bandwidth = |entry: &Entry| {
+        let seconds = entry.ns_per_iteration / 1e9;
+        format!(
+            "{:.2} GiB/s",
+            entry.bytes_per_iteration as f64 / seconds / GIB
+        )
+    };
+    let header = |report: &mut String, first: &str| {
+        let kernels: Vec<String> = COPY_KERNELS
+            .iter()
+            .map(|kernel| format!("{:<21}", kernel.title()))
+            .collect();
+        writeln!(report, "| {:<7} | {} |", first, kernels.join(" | ")).unwrap();
+        writeln!(
+            report,
+            "| ------- |{}",
+            " --------------------- |".repeat(COPY_KERNELS.len())
+        )
+        .unwrap();
+    };
+
+    header(&mut report, "Size");
+    for size in copy_sweep() {
+        let cells: Vec<String> = COPY_KERNELS
+            .iter()
+            .map(|kernel| {
+                results
+                    .get(&copy_title(*kernel, size))
+                    .map_or(String::new(), bandwidth)
+            })
+            .collect();
+        if cells.iter().all(|cell| cell.is_empty()) {
+            continue;
+        }
+        let cells: Vec<String> = cells.iter().map(|cell| format!("{:<21}", cell)).collect();
+        writeln!(
+            report,
+            "| {:<7} | {} |",
+            format_size(size),
+            cells.join(" | ")
+        )
+        .unwrap();
+    }
+
+    writeln!(report).unwrap();
+    header(&mut report, "Threads");
+    for threads in thread_counts(results, &COPY_KERNELS, copy_threaded_title) {
+        let cells: Vec<String> = COPY_KERNELS
+            .iter()
+            .map(|kernel| {
+                results
+                    .get(&copy_threaded_title(*kernel, threads))
+                    .map_or(String::new(), |entry| {
+                        format!(
+                            "{} ({})",
+                            format_time(entry.ns_per_iteration),
+                            bandwidth(entry)
+                        )
+                    })
+            })
+            .collect();
+        if cells.iter().all(|cell| cell.is_empty()) {
+            continue;
+        }
+        let cells: Vec<String> = cells.iter().map(|cell| format!("{:<21}", cell)).collect();
+        writeln!(report, "| {:<7} | {} |", threads, cells.join(" | ")).unwrap();
+    }
+
+    report
+}
+
+// The time per atomic op against how the counters share lines, then one-way core-to-core latency
+// (half a ping-pong round trip) for the pairs of cores we have a result for: a matrix when that's
+// every pair, otherwise one row per pair along with how far apart the cores are.
+pub fn contention(results: &Baseline) -> String {
+    let mut report = String::new();
+
+    let sharings: Vec<String> = SHARINGS
+        .iter()
+        .map(|sharing| format!("{:<17}", sharing.title()))
+        .collect();
+    writeln!(report, "| {:<21} | {} |", "Operation", sharings.join(" | ")).unwrap();
+    writeln!(
+        report,
+        "| --------------------- |{}",
+        " ----------------- |".repeat(SHARINGS.len())
+    )
+    .unwrap();
+    for op in ATOMIC_OPS.iter() {
+        let cells: Vec<String> = SHARINGS
+            .iter()
+            .map(|sharing| {
+                let cell = results
+                    .get(&atomic_title(*op, *sharing))
+                    .map_or(String::new(), |entry| format_time(entry.ns_per_iteration));
+                format!("{:<17}", cell)
+            })
+            .collect();
+        writeln!(report, "| {:<21} | {} |", op.title(), cells.join(" | ")).unwrap();
+    }
+
+    let cores: Vec<usize> = core_affinity::get_core_ids()
+        .unwrap_or_default()
+        .iter()
+        .map(|core| core.id)
+        .collect();
+    let latency = |first: usize, second: usize| {
+        results
+            .get(&ping_pong_title(first.min(second), first.max(second)))
+            .map(|entry| entry.ns_per_iteration / (2 * PING_PONG_ROUND_TRIPS) as f64)
+    };
+    let measured: Vec<usize> = cores
+        .iter()
+        .copied()
+        .filter(|core| cores.iter().any(|other| latency(*core, *other).is_some()))
+        .collect();
+    if measured.is_empty() {
+        return report;
+    }
+
+    let pairs: Vec<(usize, usize, f64)> = measured
+        .iter()
+        .enumerate()
+        .flat_map(|(i, first)| {
+            measured[i + 1..]
+                .iter()
+                .filter_map(move |second| Some((*first, *second, latency(*first, *second)?)))
+        })
+        .collect();
+    if pairs.len() < measured.len() * (measured.len() - 1) / 2 {
+        writeln!(report, "\n| Cores    | Distance     | One way  |").unwrap();
+        writeln!(report, "| -------- | ------------ | -------- |").unwrap();
+        for (first, second, ns) in pairs {
+            let distance = core_distance(first, second).map_or("", |distance| distance.title());
+            let cores = format!("{} and {}", first, second);
+            writeln!(
+                report,
+                "| {:<8} | {:<12} | {:<8} |",
+                cores,
+                distance,
+                format_time(ns)
+            )
+            .unwrap();
+        }
+        return report;
+    }
+
+    writeln!(report, "\nCore-to-core latency (ns, one way):").unwrap();
+    let header: Vec<String> = measured.iter().map(|core| format!("{:>5}", core)).collect();
+    writeln!(report, "{:>5} {}", "", header.join(" ")).unwrap();
+    for first in measured.iter() {
+        let row: Vec<String> = measured
+            .iter()
+            .map(|second| match latency(*first, *second) {
+                _ if first == second => format!("{:>5}", "-"),
+                Some(ns) => format!("{:>5.0}", ns),
+                None => format!("{:>5}", "?"),
+            })
+            .collect();
+        writeln!(report, "{:>5} {}", first, row.join(" ")).unwrap();
+    }
+
+    report
+}
+
+// Per layout what's in memory per element, the time per element and the bandwidth of the bytes
+// the scan uses. The gap between the last two columns is what the padding costs.
+pub fn layout(results: &Baseline) -> String {
+    const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+    let mut report = String::new();
+
+    writeln!(
+        report,
+        "| {:<44} | Element | Per element | Effective   |",
+        "Layout"
+    )
+    .unwrap();
+    writeln!(
+        report,
+        "| {} | ------- | ----------- | ----------- |",
+        "-".repeat(44)
+    )
+    .unwrap();
+    for layout in LAYOUTS.iter() {
+        let entry = match results.get(&layout_title(*layout)) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let seconds = entry.ns_per_iteration / 1e9;
+        writeln!(
+            report,
+            "| {:<44} | {:<7} | {:<11} | {:<11} |",
+            layout_title(*layout),
+            format_size(layout.element_size()),
+            format!("{:.2} ns", entry.ns_per_iteration / LAYOUT_ELEMENTS as f64),
+            format!(
+                "{:.2} GiB/s",
+                entry.bytes_per_iteration as f64 / seconds / GIB
+            )
+        )
+        .unwrap();
+    }
+
+    report
+}
+
+// Per mechanism and peer the round trip, and half of it as the cost of one switch (or one wakeup
+// across cores), on the same core and on two.
+pub fn switches(results: &Baseline) -> String {
+    let mut report = String::new();
+
+    writeln!(
+        report,
+        "| Mechanism | Peer    | Same core          | Other core         |"
+    )
+    .unwrap();
+    writeln!(
+        report,
+        "| --------- | ------- | ------------------ | ------------------ |"
+    )
+    .unwrap();
+    for mechanism in MECHANISMS.iter() {
+        for peer in [Peer::Thread, Peer::Process].iter() {
+            let cells: Vec<String> = [SwitchCores::Same, SwitchCores::Other]
+                .iter()
+                .map(|cores| {
+                    let cell = results
+                        .get(&switch_title(*mechanism, *peer, *cores))
+                        .map_or(String::new(), |entry| {
+                            format!(
+                                "{} ({} each)",
+                                format_time(entry.ns_per_iteration),
+                                format_time(entry.ns_per_iteration / 2.0)
+                            )
+                        });
+                    format!("{:<18}", cell)
+                })
+                .collect();
+            if cells.iter().all(|cell| cell.trim().is_empty()) {
+                continue;
+            }
+            writeln!(
+                report,
+                "| {:<9} | {:<7} | {} |",
+                mechanism.as_str(),
+                peer.as_str(),
+                cells.join(" | ")
+            )
+            .unwrap();
+        }
+    }
+
+    report
+}
+
+// Per syscall the libc path against the raw one. Where libc is much faster than raw it never
+// entered the kernel, the vDSO or a cache answered.
+pub fn syscalls(results: &Baseline) -> String {
+    let mut report = String::new();
+
+    writeln!(report, "| Syscall       | libc / vDSO | Raw syscall |").unwrap();
+    writeln!(report, "| ------------- | ----------- | ----------- |").unwrap();
+    for call in SYSCALLS.iter() {
+        let cells: Vec<String> = [SyscallPath::Libc, SyscallPath::Raw]
+            .iter()
+            .map(|path| {
+                let cell = results
+                    .get(&syscall_title(*call, *path))
+                    .map_or(String::new(), |entry| {
+                        format!("{:.0} ns", entry.ns_per_iteration)
+                    });
+                format!("{:<11}", cell)
+            })
+            .collect();
+        if cells.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        writeln!(report, "| {:<13} | {} |", call.as_str(), cells.join(" | ")).unwrap();
+    }
+
+    report
+}
+
+// Per way of creating a thread or process the mean and the tail, creation is where the tail
+// (page table copies, scheduler placement) tends to hurt.
+pub fn spawn(results: &Baseline) -> String {
+    let mut report = String::new();
+
+    writeln!(report, "| {:<29} | Per creation |", "Creation").unwrap();
+    writeln!(report, "| {} | ------------ |", "-".repeat(29)).unwrap();
+    for creation in CREATIONS.iter() {
+        if let Some(entry) = results.get(creation.title()) {
+            writeln!(
+                report,
+                "| {:<29} | {:<12} |",
+                creation.title(),
+                format_time(entry.ns_per_iteration)
+            )
+            .unwrap();
+        }
+    }
+
+    report
+}
+
+// Per kind of fault the time per iteration and per page faulted in, then the minor fault scaled up
+// to a heap, which is what a process pays the first time it touches its memory.
+pub fn faults(results: &Baseline) -> String {
+    const HEAP: usize = 1 << 30;
+    let mut report = String::new();
+
+    writeln!(report, "| {:<34} | Per call | Per page |", "Operation").unwrap();
+    writeln!(report, "| {} | -------- | -------- |", "-".repeat(34)).unwrap();
+    for fault in crate::benchmarks::faults() {
+        let title = fault_title(fault);
+        let entry = match results.get(&title) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let per_page = match fault.pages_per_iteration() {
+            0 => String::new(),
+            pages => format_time(entry.ns_per_iteration / pages as f64),
+        };
+        writeln!(
+            report,
+            "| {:<34} | {:<8} | {:<8} |",
+            title,
+            format_time(entry.ns_per_iteration),
+            per_page
+        )
+        .unwrap();
+    }
+
+    if let Some(minor) = results.get(&fault_title(Fault::Minor)) {
+        writeln!(
+            report,
+            "\nFaulting in {} of fresh {} pages: {}",
+            format_size(HEAP),
+            format_size(page_size()),
+            format_time(minor.ns_per_iteration * (HEAP / page_size()) as f64)
+        )
+        .unwrap();
+    }
+
+    report
+}
+
+// Per operation the time each allocator takes for one of it, then small blocks per thread count as
+// each thread sees them, which is where a global lock or a shared arena shows.
+pub fn allocators(results: &Baseline) -> String {
+    let mut report = String::new();
+    let header = |report: &mut String, first: &str| {
+        let allocators: Vec<String> = ALLOCATORS
+            .iter()
+            .map(|allocator| format!("{:<8}", allocator.as_str()))
+            .collect();
+        writeln!(report, "| {:<36} | {} |", first, allocators.join(" | ")).unwrap();
+        writeln!(
+            report,
+            "| {} |{}",
+            "-".repeat(36),
+            " -------- |".repeat(ALLOCATORS.len())
+        )
+        .unwrap();
+    };
+    let row = |report: &mut String, first: &str, cells: Vec<String>| {
+        if cells.iter().all(|cell| cell.is_empty()) {
+            return;
+        }
+        let cells: Vec<String> = cells.iter().map(|cell| format!("{:<8}", cell)).collect();
+        writeln!(report, "| {:<36} | {} |", first, cells.join(" | ")).unwrap();
+    };
+
+    header(&mut report, "Per operation");
+    for op in ALLOC_OPS.iter() {
+        let cells = ALLOCATORS
+            .iter()
+            .map(|allocator| {
+                results
+                    .get(&alloc_title(*allocator, *op))
+                    .map_or(String::new(), |entry| {
+                        format_time(entry.ns_per_iteration / op.ops_per_iteration() as f64)
+                    })
+            })
+            .collect();
+        row(&mut report, &op.title(), cells);
+    }
+
+    writeln!(report).unwrap();
+    header(&mut report, "malloc + free 64 B per thread");
+    for threads in thread_counts(results, &ALLOCATORS, alloc_threaded_title) {
+        let cells = ALLOCATORS
+            .iter()
+            .map(|allocator| {
+                results
+                    .get(&alloc_threaded_title(*allocator, threads))
+                    .map_or(String::new(), |entry| {
+                        format_time(entry.ns_per_iteration / THREADED_ALLOCATIONS as f64)
+                    })
+            })
+            .collect();
+        let unit = if threads == 1 { "thread" } else { "threads" };
+        row(&mut report, &format!("{} {}", threads, unit), cells);
+    }
+
+    writeln!(
+        report,
+        "\nEverything else in this build allocates with {}.",
+        global_allocator().as_str()
+    )
+    .unwrap();
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_napkin_boundaries() {
+        let cases = [
+            (0.47, 0.5),
+            (17.88, 20.0),
+            (304.0, 300.0),
+            (1.0, 1.0),
+            // Halfway rounds away from zero, into the next magnitude at 9.5.
+            (149.0, 100.0),
+            (150.0, 200.0),
+            (95.0, 100.0),
+            (0.05, 0.05),
+            (0.0, 0.0),
+            (-3.0, -3.0),
+            (f64::INFINITY, f64::INFINITY),
+        ];
+        for (value, rounded) in cases.iter() {
+            let got = round_napkin(*value);
+            assert!(
+                got == *rounded || ((got - rounded) / rounded).abs() < 1e-12,
+                "round_napkin({}) = {}, not {}",
+                value,
+                got,
+                rounded
+            );
+        }
+        assert!(round_napkin(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn format_time_units() {
+        let cases = [
+            (0.0, "0 ns"),
+            (0.47, "0.5 ns"),
+            (17.88, "20 ns"),
+            (999.0, "1 μs"),
+            (1_000.0, "1 μs"),
+            (1.5e6, "2 ms"),
+            (304e6, "300 ms"),
+            (1e9, "1s"),
+            (30e9, "30s"),
+            (60e9, "1m"),
+            // 90s is 1.5m, which rounds to 2m rather than printing a fraction.
+            (90e9, "2m"),
+        ];
+        for (nanoseconds, formatted) in cases.iter() {
+            assert_eq!(format_time(*nanoseconds), *formatted, "{} ns", nanoseconds);
+        }
+    }
+
+    #[test]
+    fn plateau_detection() {
+        let sweep = |latencies: &[f64]| -> Vec<(usize, f64)> {
+            latencies
+                .iter()
+                .enumerate()
+                .map(|(i, ns)| (1 << i, *ns))
+                .collect()
+        };
+        // (case, latencies, sizes on each plateau)
+        let cases = [
+            ("empty", vec![], Vec::<Vec<usize>>::new()),
+            ("flat", vec![1.0, 1.1, 1.2], vec![vec![1, 2, 4]]),
+            (
+                "two levels, a lone size past them dropped",
+                vec![1.0, 1.1, 1.2, 3.0, 3.1, 10.0],
+                vec![vec![1, 2, 4], vec![8, 16]],
+            ),
+            // Under PLATEAU_JUMP of the fastest size on the plateau, however long it creeps.
+            ("creeping", vec![1.0, 1.3, 1.39], vec![vec![1, 2, 4]]),
+            (
+                "just past the jump",
+                vec![1.0, 1.1, 1.41, 1.5],
+                vec![vec![1, 2], vec![4, 8]],
+            ),
+        ];
+        for (case, latencies, expected) in cases.iter() {
+            let points = sweep(latencies);
+            let sizes: Vec<Vec<usize>> = plateaus(&points)
+                .iter()
+                .map(|plateau| plateau.iter().map(|(size, _)| *size).collect())
+                .collect();
+            assert_eq!(sizes, *expected, "{}", case);
+        }
+    }
+}